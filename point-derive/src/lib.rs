@@ -3,6 +3,9 @@ use quote::quote;
 use syn::DeriveInput;
 use syn::parse_macro_input;
 
+// Orders points by z(), then x(), then y(), which is the order a board keeps
+// its cells in: a layer at a time, going down each column. z() is always 0
+// on flat lattices.
 #[proc_macro_derive(OrdForPoint)]
 pub fn derive_pt_impl(token_stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input: syn::DeriveInput = parse_macro_input!(token_stream as DeriveInput);
@@ -15,6 +18,10 @@ pub fn derive_pt_impl(token_stream: proc_macro::TokenStream) -> proc_macro::Toke
         }
         impl Ord for #ident {
             fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+                if self.z() != other.z() {
+                    return self.z().cmp(&other.z());
+                }
+
                 if self.x() < other.x() {
                     return Ordering::Less;
                 }
@@ -28,7 +35,7 @@ pub fn derive_pt_impl(token_stream: proc_macro::TokenStream) -> proc_macro::Toke
         }
         impl PartialEq for #ident {
             fn eq(&self, other: &Self) -> bool {
-                self.x() == other.x() && self.y() == other.y()
+                self.x() == other.x() && self.y() == other.y() && self.z() == other.z()
             }
         }
         impl Eq for #ident {}
@@ -36,6 +43,7 @@ pub fn derive_pt_impl(token_stream: proc_macro::TokenStream) -> proc_macro::Toke
             fn hash<H :std::hash::Hasher>(&self, state: &mut H) {
                 self.x().hash(state);
                 self.y().hash(state);
+                self.z().hash(state);
             }
         }
     };
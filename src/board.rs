@@ -1,20 +1,21 @@
-use std::cmp;
 use std::fmt;
-use std::fs::File;
-use std::io::BufRead;
-use std::io::BufReader;
-use std::io::Error;
+use std::fs;
 use std::ops::Range;
 
+use crate::error::ParseError;
+use crate::point::Lattice;
 use crate::point::Point;
 use crate::point::SimplePoint;
 use crate::polyomino::Polyomino;
+use crate::utils;
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum BoardState<'a, P: Polyomino> {
     Void,  // Out of bounds/a hole in the board
     Empty, // A valid part of the board, but no piece is there
-    Full(&'a P, usize, i16, i16), // Has a piece
+    Full(&'a P, usize, i16, i16, i16), // Has a piece, with its origin at the last three numbers
+    Fixed(char), // Filled before the search starts, by the piece with this label
+    Reserved, // A valid part of the board that has to stay uncovered
 }
 
 impl <'a, P> Copy for BoardState<'a, P> where P: Polyomino {}
@@ -30,7 +31,12 @@ impl<'a, P:Polyomino> BoardState<'a, P> {
         match *self {
             BoardState::Void => " ".to_string(),
             BoardState::Empty => ".".to_string(),
-            BoardState::Full(poly, pt_idx, _, _) => poly.get_nth(pt_idx).unwrap().to_string(),
+            BoardState::Full(poly, pt_idx, ..) => match poly.name() {
+                Some(name) => name.to_string(),
+                None => poly.get_nth(pt_idx).unwrap().to_string(),
+            },
+            BoardState::Fixed(label) => label.to_string(),
+            BoardState::Reserved => ".".to_string(),
         }
     }
 
@@ -38,7 +44,9 @@ impl<'a, P:Polyomino> BoardState<'a, P> {
         match *self {
             BoardState::Void => other == BoardState::Void,
             BoardState::Empty => other == BoardState::Empty,
-            BoardState::Full(p, _, _, _) => if let BoardState::Full(p1, _, _, _) = other { p == p1 } else { false }
+            BoardState::Full(p, ..) => if let BoardState::Full(p1, ..) = other { p == p1 } else { false }
+            BoardState::Fixed(label) => other == BoardState::Fixed(label),
+            BoardState::Reserved => other == BoardState::Reserved,
         }
     }
 }
@@ -47,11 +55,29 @@ impl<'a, P:Polyomino> BoardState<'a, P> {
 pub struct Board<'a, P:Polyomino> {
     height: i16,
     width: i16,
+    depth: i16,
     board: Vec<BoardState<'a, P>>,
+    labels: Vec<Option<String>>, // Empty unless some cell has a label
 }
 
 impl<'a, P:Polyomino> fmt::Display for Board<'a, P> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Lines between triangles or hexagons don't fit on a grid of characters, so each
+        // cell is just shown as it would be drawn in a board file
+        if P::Pt::LATTICE != Lattice::Square {
+            for z in 0..self.get_depth() {
+                if z > 0 {
+                    writeln!(f, "-")?;
+                }
+                for y in 0..self.height {
+                    let row: String = self.col_range().map(|x| self.get_3d(x, y, z).rep()).collect();
+                    writeln!(f, "{}", row.trim_end())?;
+                }
+            }
+
+            return Ok(());
+        }
+
         fn print_top_row_border<P: Polyomino>(s: &Board<P>, f: &mut fmt::Formatter) -> fmt::Result {
             f.write_str(if s.get(0, 0) == BoardState::Void {
                 " "
@@ -135,92 +161,299 @@ impl<'a, P:Polyomino> Board<'a, P> {
         Board {
             height: h,
             width: w,
+            depth: 1,
             board: vec![BoardState::Empty; (h * w) as usize],
+            labels: Vec::new(),
         }
     }
 
-    pub fn from_file(name: &str) -> Result<Board<'_, P>, Error> {
-        let f = File::open(name)?;
+    // A box of w by h cells in each of d layers. Flat lattices only have one
+    // layer.
+    pub fn new_box(w: i16, h: i16, d: i16) -> Board<'a, P> {
+        let depth = if P::Pt::LATTICE == Lattice::Cubic { d } else { 1 };
 
-        let buf_file = BufReader::new(&f);
+        Board {
+            height: h,
+            width: w,
+            depth,
+            board: vec![BoardState::Empty; (h * w * depth) as usize],
+            labels: Vec::new(),
+        }
+    }
+
+    pub fn from_file(name: &str) -> Result<Board<'a, P>, ParseError> {
+        Board::from_string(&fs::read_to_string(name)?)
+    }
+
+    // The cells come first. They can be followed by a blank line and a label
+    // for each cell, one line for each row of the board with the labels of
+    // its cells separated by spaces. A '-' leaves a cell without a label. A
+    // solid board has its layers one after the other, with a line of dashes
+    // between them.
+    pub fn from_string(contents: &str) -> Result<Board<'a, P>, ParseError> {
+        let all_lines: Vec<&str> = contents.lines().collect();
+        let (lines, label_lines) = match all_lines.iter().position(|line| line.is_empty()) {
+            Some(blank) if all_lines[blank..].iter().any(|line| !line.trim().is_empty()) =>
+                (&all_lines[..blank], Some(&all_lines[blank + 1..])),
+            _ => (&all_lines[..], None),
+        };
+
+        let layers = split_layers::<P>(lines, 0);
+        let rows = layers.iter().flat_map(|(_, rows)| rows.iter());
+        let mut b = Board::new_box(
+            rows.clone().map(|row| row.chars().count()).max().unwrap_or(0) as i16,
+            layers.iter().map(|(_, rows)| rows.len()).max().unwrap_or(0) as i16,
+            layers.len() as i16,
+        );
+        let mut cells = 0;
+
+        // We could treat each space as a void, but trailing spaces may not
+        // exist. Instead, mark every cell as void and then mark the cells
+        // that are there.
+        b.board.fill(BoardState::Void);
+
+        for (z, &(first_line, rows)) in layers.iter().enumerate() {
+            for (y, line) in rows.iter().enumerate() {
+                // Any other letter is a cell filled by a pre-placed piece, and a
+                // '.' is a cell that has to stay uncovered
+                for (x, c) in line.chars().enumerate() {
+                    let idx = b.to_idx(x as i16, y as i16, z as i16);
+
+                    if utils::is_cell(c) {
+                        b.board[idx] = BoardState::Empty;
+                        cells += 1;
+                    } else if c == '.' {
+                        b.board[idx] = BoardState::Reserved;
+                        cells += 1;
+                    } else if c.is_ascii_alphabetic() {
+                        b.board[idx] = BoardState::Fixed(c);
+                        cells += 1;
+                    } else if c != ' ' {
+                        return Err(ParseError::InvalidCharacter { line: first_line + y + 1, column: x + 1, found: c });
+                    }
+                }
+            }
+        }
+
+        if cells == 0 {
+            return Err(ParseError::Empty);
+        }
+
+        // The cells of each pre-placed piece must be joined up
+        for (_, piece) in b.fixed_pieces() {
+            let mut joined = vec![false; piece.len()];
+            let mut pending = vec![0];
+            joined[0] = true;
+
+            while let Some(i) = pending.pop() {
+                let (x, y, z) = (piece[i].x, piece[i].y, piece[i].z);
+                for (j, pt) in piece.iter().enumerate() {
+                    if !joined[j] && P::Pt::neighbours(x, y).contains(&(pt.x - x, pt.y - y, pt.z - z)) {
+                        joined[j] = true;
+                        pending.push(j);
+                    }
+                }
+            }
 
-        let mut board_x: i16 = 0;
-        let mut board_y: i16 = 0;
-        let mut lines = Vec::new();
+            if let Some(i) = joined.iter().position(|&j| !j) {
+                let first_line = layers[piece[i].z as usize].0;
+                return Err(ParseError::DisconnectedPiece { line: first_line + piece[i].y as usize + 1, column: piece[i].x as usize + 1 });
+            }
+        }
 
-        for wline in buf_file.lines() {
-            let line = wline.unwrap();
-            board_x = cmp::max(board_x, line.len() as i16);
-            lines.push(line);
-            board_y += 1;
+        if let Some(label_lines) = label_lines {
+            b.read_labels(label_lines, lines.len() + 2)?;
         }
 
-        let mut b = Board::new(board_x, board_y);
+        Ok(b)
+    }
 
-        for (y, line) in lines.into_iter().enumerate() {
-            // We could treat each space as a void, but trailing
-            // spaces may not exist. Instead, mark each space as
-            // void and then mark spaces as empty
+    // Gives the cells of each row the labels on the matching line, with the
+    // layers of a solid board split up as they are in the cells. The first
+    // line is line `first_line` of the file.
+    fn read_labels(&mut self, lines: &[&str], first_line: usize) -> Result<(), ParseError> {
+        let layers = split_layers::<P>(lines, first_line);
 
-            for x in 0..board_x {
-                let idx = b.to_idx(x, y as i16);
-                b.board[idx] = BoardState::Void;
+        for z in 0..layers.len().max(self.depth as usize) {
+            let (start, lines) = layers.get(z).copied().unwrap_or((first_line + lines.len(), &[]));
+            let rows = lines.len().max(self.height as usize);
+
+            for y in 0..rows {
+                let labels: Vec<&str> = lines.get(y).map_or(Vec::new(), |line| line.split_whitespace().collect());
+                let cells: Vec<i16> = self.col_range()
+                    .filter(|&x| y < self.height as usize && self.get_3d(x, y as i16, z as i16) != BoardState::Void)
+                    .collect();
+
+                if labels.len() != cells.len() {
+                    return Err(ParseError::LabelCount { line: start + y, cells: cells.len(), found: labels.len() });
+                }
+
+                for (x, label) in cells.into_iter().zip(labels) {
+                    if label != "-" {
+                        self.set_label_3d(x, y as i16, z as i16, label);
+                    }
+                }
             }
+        }
 
-            for (x, c) in line.chars().enumerate() {
-                if c != ' ' {
-                    let idx = b.to_idx(x as i16, y as i16);
-                    b.board[idx] = BoardState::Empty;
+        Ok(())
+    }
+
+    // Fills the cells `p` would cover, with its origin at `ll`, with a piece
+    // that is there before the search starts. If one of the candidates has the
+    // same shape, the solver treats it as already used.
+    pub fn fix_polyomino(&mut self, p: &P, ll: &SimplePoint, label: char) -> bool {
+        if !self.can_add_polyomino(p, ll) {
+            return false;
+        }
+
+        for pt in p.iter() {
+            self.set_3d(pt.x() + ll.x, pt.y() + ll.y, pt.z() + ll.z, BoardState::Fixed(label));
+        }
+
+        true
+    }
+
+    // The cells of each pre-placed piece, in the order their labels are first
+    // found in column-major order
+    pub fn fixed_pieces(&self) -> Vec<(char, Vec<SimplePoint>)> {
+        let mut res: Vec<(char, Vec<SimplePoint>)> = Vec::new();
+
+        for pt in self.points() {
+            if let BoardState::Fixed(label) = self.get_3d(pt.x, pt.y, pt.z) {
+                match res.iter_mut().find(|(l, _)| *l == label) {
+                    Some((_, cells)) => cells.push(pt),
+                    None => res.push((label, vec![pt])),
                 }
             }
         }
-        Ok(b)
+
+        res
     }
 
-    fn to_idx(&self, x: i16, y: i16) -> usize {
-        (x * self.height + y) as usize
+    // The label of the cell in the first layer, if it has one
+    pub fn label(&self, x: i16, y: i16) -> Option<&str> {
+        self.label_3d(x, y, 0)
+    }
+
+    pub fn label_3d(&self, x: i16, y: i16, z: i16) -> Option<&str> {
+        if !self.on_board(x, y, z) {
+            return None;
+        }
+
+        self.labels.get(self.to_idx(x, y, z)).and_then(|l| l.as_deref())
+    }
+
+    pub fn set_label(&mut self, x: i16, y: i16, label: &str) {
+        self.set_label_3d(x, y, 0, label);
+    }
+
+    fn set_label_3d(&mut self, x: i16, y: i16, z: i16, label: &str) {
+        if self.labels.is_empty() {
+            self.labels = vec![None; self.board.len()];
+        }
+
+        let idx = self.to_idx(x, y, z);
+        self.labels[idx] = Some(label.to_string());
+    }
+
+    // The cells with the given label, in column-major order
+    pub fn labeled(&self, label: &str) -> Vec<SimplePoint> {
+        self.points()
+            .filter(|pt| self.label_3d(pt.x, pt.y, pt.z) == Some(label))
+            .collect()
+    }
+
+    // Every position on the board, void or not, layer by layer and then in
+    // column-major order
+    pub fn points(&self) -> impl Iterator<Item = SimplePoint> + '_ {
+        (0..self.depth).flat_map(move |z| {
+            self.col_range().flat_map(move |x| self.row_range().map(move |y| SimplePoint::new_3d(x, y, z)))
+        })
+    }
+
+    // Where the cell is in `board`, and in anything that has an entry for
+    // each cell such as the numbers from board_utils::number_pieces
+    pub(crate) fn to_idx(&self, x: i16, y: i16, z: i16) -> usize {
+        ((z * self.width + x) * self.height + y) as usize
+    }
+
+    // The position of the cell at an index of `board`
+    fn to_point(&self, idx: usize) -> SimplePoint {
+        let (height, width) = (self.height as usize, self.width as usize);
+        SimplePoint::new_3d((idx / height % width) as i16, (idx % height) as i16, (idx / (height * width)) as i16)
     }
 
     pub fn erase(&mut self, x: i16, y: i16) {
         self.set(x, y, BoardState::Void);
     }
 
+    // Marks an empty cell as one that no piece may cover
+    pub fn reserve(&mut self, pt: &SimplePoint) -> bool {
+        if self.get_3d(pt.x, pt.y, pt.z) != BoardState::Empty {
+            return false;
+        }
+
+        self.set_3d(pt.x, pt.y, pt.z, BoardState::Reserved);
+        true
+    }
+
+    // Undoes reserve
+    pub fn release(&mut self, pt: &SimplePoint) {
+        if self.get_3d(pt.x, pt.y, pt.z) == BoardState::Reserved {
+            self.set_3d(pt.x, pt.y, pt.z, BoardState::Empty);
+        }
+    }
+
     fn set(&mut self, x: i16, y: i16, state: BoardState<'a, P>) {
-        let idx = self.to_idx(x, y);
+        self.set_3d(x, y, 0, state);
+    }
+
+    fn set_3d(&mut self, x: i16, y: i16, z: i16, state: BoardState<'a, P>) {
+        let idx = self.to_idx(x, y, z);
         self.board[idx] = state;
     }
 
+    // The cell in the first layer, which is the only one of a flat board
     pub fn get(&self, x: i16, y: i16) -> BoardState<'a, P> {
-        if self.on_board(x, y) {
-            return self.board[self.to_idx(x, y)];
+        self.get_3d(x, y, 0)
+    }
+
+    pub fn get_3d(&self, x: i16, y: i16, z: i16) -> BoardState<'a, P> {
+        if self.on_board(x, y, z) {
+            return self.board[self.to_idx(x, y, z)];
         }
 
         BoardState::Void
     }
 
+    pub fn can_add_polyomino(&self, p: &P, ll: &SimplePoint) -> bool {
+        P::Pt::is_translation(ll.x, ll.y)
+            && p.iter().all(|&pt| self.get_3d(pt.x() + ll.x, pt.y() + ll.y, pt.z() + ll.z) == BoardState::Empty)
+    }
+
     pub fn add_polyomino<'b>(&mut self, p: &'a P, ll: &'b SimplePoint) -> bool {
-        if p.iter().any(|&pt| self.get(pt.x() + ll.x(), pt.y() + ll.y()) != BoardState::Empty)
-        {
+        if !self.can_add_polyomino(p, ll) {
             return false;
         }
 
         for (idx, pt) in p.iter().enumerate() {
-            self.set(pt.x() + ll.x(), pt.y() + ll.y(), BoardState::Full(p, idx, ll.x(), ll.y()));
+            self.set_3d(pt.x() + ll.x, pt.y() + ll.y, pt.z() + ll.z, BoardState::Full(p, idx, ll.x, ll.y, ll.z));
         }
 
         true
     }
 
     pub fn remove_polyomino(&mut self, ll: &SimplePoint) {
-        if let BoardState::Full(p, _, start_x, start_y) = self.get(ll.x(), ll.y()) {
+        if let BoardState::Full(p, _, start_x, start_y, start_z) = self.get_3d(ll.x, ll.y, ll.z) {
             for pt in p.iter() {
-                self.set(pt.x() + start_x, pt.y() + start_y, BoardState::Empty);
+                self.set_3d(pt.x() + start_x, pt.y() + start_y, pt.z() + start_z, BoardState::Empty);
             }
         }
     }
 
-    fn on_board(&self, x: i16, y: i16) -> bool {
-        x >= 0 && y >= 0 && x < self.width && y < self.height
+    fn on_board(&self, x: i16, y: i16, z: i16) -> bool {
+        x >= 0 && y >= 0 && z >= 0 && x < self.width && y < self.height && z < self.depth
     }
 
     pub fn get_height(&self) -> i16 {
@@ -230,6 +463,11 @@ impl<'a, P:Polyomino> Board<'a, P> {
     pub fn get_width(&self) -> i16 {
         self.width
     }
+
+    // The number of layers, which is 1 on a flat lattice
+    pub fn get_depth(&self) -> i16 {
+        self.depth
+    }
     
     pub fn row_range(&self) -> Range<i16> {
         0..self.height
@@ -240,6 +478,22 @@ impl<'a, P:Polyomino> Board<'a, P> {
     }
 }
 
+// The layers of a solid board, each with the line of the file it starts on,
+// where the first of `lines` is line `first_line`. Flat boards have one layer.
+fn split_layers<'b, P:Polyomino>(lines: &'b [&'b str], first_line: usize) -> Vec<(usize, &'b [&'b str])> {
+    let mut layers = Vec::new();
+    let mut start = 0;
+    for (n, line) in lines.iter().enumerate() {
+        if P::Pt::LATTICE == Lattice::Cubic && utils::is_layer_break(line) {
+            layers.push((first_line + start, &lines[start..n]));
+            start = n + 1;
+        }
+    }
+    layers.push((first_line + start, &lines[start..]));
+
+    layers
+}
+
 pub mod board_utils {
     use std::collections::VecDeque;
     
@@ -252,39 +506,16 @@ pub mod board_utils {
     use crate::polyomino::Polyomino;
 
     pub fn get_first_unoccupied<P:Polyomino>(b: &Board<P>) -> Option<SimplePoint> {
-        for i in 0..b.board.len() {
-            if b.board[i] == BoardState::Empty {
-                return Some(SimplePoint::new(
-                    i as i16 / b.height,
-                    i as i16 % b.height,
-                ));
-            }
-        }
-
-        None
+        b.board.iter().position(|s| *s == BoardState::Empty).map(|i| b.to_point(i))
     }
 
     pub fn get_adjacent<P:Polyomino>(p: SimplePoint, b: &Board<P>) -> FxHashSet<SimplePoint> {
         let mut adj = FxHashSet::default();
 
-        // UP
-        if p.y != 0 && b.get(p.x, p.y - 1) == BoardState::Empty {
-            adj.insert(SimplePoint::new(p.x, p.y - 1));
-        }
-
-        // LEFT
-        if p.x != 0 && b.get(p.x - 1, p.y) == BoardState::Empty {
-            adj.insert(SimplePoint::new(p.x - 1, p.y));
-        }
-
-        // DOWN
-        if p.y != b.height - 1 && b.get(p.x, p.y + 1) == BoardState::Empty {
-            adj.insert(SimplePoint::new(p.x, p.y + 1));
-        }
-
-        // RIGHT
-        if p.x != b.width - 1 && b.get(p.x + 1, p.y) == BoardState::Empty {
-            adj.insert(SimplePoint::new(p.x + 1, p.y));
+        for &(dx, dy, dz) in P::Pt::neighbours(p.x, p.y) {
+            if b.get_3d(p.x + dx, p.y + dy, p.z + dz) == BoardState::Empty {
+                adj.insert(SimplePoint::new_3d(p.x + dx, p.y + dy, p.z + dz));
+            }
         }
 
         adj
@@ -294,7 +525,7 @@ pub mod board_utils {
     pub fn get_all_adjacent<P:Polyomino>(p: SimplePoint, b: &Board<P>) -> FxHashSet<SimplePoint> {
         let mut region = FxHashSet::default();
 
-        if b.get(p.x, p.y) != BoardState::Empty {
+        if b.get_3d(p.x, p.y, p.z) != BoardState::Empty {
             return region;
         }
 
@@ -319,6 +550,145 @@ pub mod board_utils {
         region
     }
 
+    pub fn get_all_unoccupied<P:Polyomino>(b: &Board<P>) -> Vec<SimplePoint> {
+        b.points()
+            .filter(|pt| b.get_3d(pt.x, pt.y, pt.z) == BoardState::Empty)
+            .collect()
+    }
+
+    // The candidates that are already on the board as pre-placed pieces. Each
+    // pre-placed piece is matched to a different candidate with the same shape,
+    // in any orientation, preferring one named by the piece's label. One with
+    // no match just fills its cells.
+    pub fn fixed_candidates<P:Polyomino>(b: &Board<P>, candidates: &[Vec<P>]) -> Vec<usize> {
+        let mut res = Vec::new();
+
+        for (label, cells) in b.fixed_pieces() {
+            let shape = P::new(cells.iter().map(|pt| P::Pt::new_3d(pt.x, pt.y, pt.z)).collect());
+            let fits = |i: &usize| !res.contains(i) && candidates[*i].first().is_some_and(|p| p.is_congruent_to(&shape));
+
+            let matching = (0..candidates.len())
+                .find(|i| fits(i) && candidates[*i][0].name() == Some(label))
+                .or_else(|| (0..candidates.len()).find(fits));
+
+            if let Some(i) = matching {
+                res.push(i);
+            }
+        }
+
+        res
+    }
+
+    pub fn count_unoccupied<P:Polyomino>(b: &Board<P>) -> usize {
+        b.board.iter().filter(|s| **s == BoardState::Empty).count()
+    }
+
+    // Numbers the pieces on the board, placed or pre-placed, in the order they
+    // are found going down each column of each layer, and gives each cell the number of the
+    // piece covering it. Copies of a piece are told apart by where they are.
+    pub fn number_pieces<P:Polyomino>(b: &Board<P>) -> Vec<Option<usize>> {
+        let same = |a: &BoardState<P>, b: &BoardState<P>| match (a, b) {
+            (BoardState::Full(p, _, ax, ay, az), BoardState::Full(q, _, bx, by, bz)) => std::ptr::eq(*p, *q) && (ax, ay, az) == (bx, by, bz),
+            (BoardState::Fixed(l), BoardState::Fixed(m)) => l == m,
+            _ => false,
+        };
+        let mut owners: Vec<BoardState<P>> = Vec::new();
+
+        b.board.iter()
+            .map(|s| {
+                if !matches!(s, BoardState::Full(..) | BoardState::Fixed(_)) {
+                    return None;
+                }
+
+                let n = owners.iter().position(|o| same(o, s)).unwrap_or_else(|| {
+                    owners.push(*s);
+                    owners.len() - 1
+                });
+                Some(n)
+            })
+            .collect()
+    }
+
+    // Measures empty regions of the board. The scanner keeps its scratch space
+    // between calls, as it is used after every placement.
+    #[derive(Default)]
+    pub struct RegionScanner {
+        seen: Vec<u32>,
+        stamp: u32,
+        pending: Vec<usize>,
+    }
+
+    impl RegionScanner {
+        pub fn new() -> RegionScanner {
+            RegionScanner::default()
+        }
+
+        // Adds the sizes of the empty regions that include any of the starting
+        // points to `sizes`. Regions with more than `limit` cells are left out.
+        pub fn get_region_sizes<P:Polyomino>(&mut self, b: &Board<P>, starts: impl Iterator<Item = SimplePoint>,
+                                             limit: usize, sizes: &mut Vec<usize>) {
+            if self.seen.len() != b.board.len() || self.stamp > u32::MAX - b.board.len() as u32 {
+                self.seen = vec![0; b.board.len()];
+                self.stamp = 0;
+            }
+
+            // Every region that is measured gets its own stamp, so cells from an
+            // earlier region that was too large to finish can be told apart
+            let first_stamp = self.stamp + 1;
+
+            for start in starts {
+                if !b.on_board(start.x, start.y, start.z) {
+                    continue;
+                }
+
+                let start = b.to_idx(start.x, start.y, start.z);
+                if self.seen[start] >= first_stamp || !matches!(b.board[start], BoardState::Empty) {
+                    continue;
+                }
+
+                self.stamp += 1;
+                self.seen[start] = self.stamp;
+                self.pending.clear();
+                self.pending.push(start);
+
+                let mut size = 1;
+                'fill: while size <= limit {
+                    let Some(next) = self.pending.pop() else {
+                        break;
+                    };
+
+                    let SimplePoint { x, y, z } = b.to_point(next);
+
+                    for &(dx, dy, dz) in P::Pt::neighbours(x, y) {
+                        if !b.on_board(x + dx, y + dy, z + dz) {
+                            continue;
+                        }
+
+                        let adj = b.to_idx(x + dx, y + dy, z + dz);
+                        if self.seen[adj] == self.stamp || !matches!(b.board[adj], BoardState::Empty) {
+                            continue;
+                        }
+
+                        // Regions that were finished are never reached again, so this
+                        // must be part of a region that was too large
+                        if self.seen[adj] >= first_stamp {
+                            size = limit + 1;
+                            break 'fill;
+                        }
+
+                        self.seen[adj] = self.stamp;
+                        self.pending.push(adj);
+                        size += 1;
+                    }
+                }
+
+                if size <= limit {
+                    sizes.push(size);
+                }
+            }
+        }
+    }
+
     #[allow(dead_code)]
     pub fn fit<'a, P:Polyomino>(b: &mut Board<'a, P>, p: &'a P) -> Option<SimplePoint> {
         /* Attempt to fit the polyomino at the first unoccuped spot on the board. */
@@ -343,9 +713,10 @@ pub mod board_utils {
         if let Some(poly_pt) = p.iter().next() {
             if poly_pt.x() <= target_pt.x()
                 && poly_pt.y() <= target_pt.y()
+                && poly_pt.z() <= target_pt.z()
                 && b.add_polyomino(
                     p,
-                    &SimplePoint::new(target_pt.x() - poly_pt.x(), target_pt.y() - poly_pt.y()),
+                    &SimplePoint::new_3d(target_pt.x() - poly_pt.x(), target_pt.y() - poly_pt.y(), target_pt.z() - poly_pt.z()),
                 )
             {
                 return true;
@@ -357,10 +728,13 @@ pub mod board_utils {
 }
 
 #[cfg(test)]
+#[allow(clippy::vec_init_then_push, clippy::assertions_on_constants)]
 mod tests {
     use crate::board::board_utils;
     use crate::board::Board;
     use crate::board::BoardState;
+    use crate::error::ParseError;
+    use crate::point::CubePoint;
     use crate::point::Point;
     use crate::point::SimplePoint;
     use crate::polyomino::Polyomino;
@@ -440,16 +814,28 @@ mod tests {
         let mut b = Board::new(12, 5);
         b.add_polyomino(&w, &SimplePoint::new(0, 0));
         b.add_polyomino(&l, &SimplePoint::new(4, 0));
-        assert_eq!(b.get(0, 0), BoardState::Full(&w, 0, 0, 0));
-        assert_eq!(b.get(4, 0), BoardState::Full(&l, 0, 4, 0));
-        assert_eq!(b.get(4, 3), BoardState::Full(&l, 3, 4, 0));
+        assert_eq!(b.get(0, 0), BoardState::Full(&w, 0, 0, 0, 0));
+        assert_eq!(b.get(4, 0), BoardState::Full(&l, 0, 4, 0, 0));
+        assert_eq!(b.get(4, 3), BoardState::Full(&l, 3, 4, 0, 0));
         b.remove_polyomino(&SimplePoint::new(1, 1));
         assert_eq!(b.get(0, 0), BoardState::Empty);
-        assert_eq!(b.get(4, 0), BoardState::Full(&l, 0, 4, 0));
+        assert_eq!(b.get(4, 0), BoardState::Full(&l, 0, 4, 0, 0));
         b.remove_polyomino(&SimplePoint::new(4, 2));
         assert_eq!(b.get(4, 0), BoardState::Empty);
     }
 
+    #[test]
+    fn test_show_names() {
+        let mut domino = SimplePolyomino::new(vec![SimplePoint::new(0, 0), SimplePoint::new(1, 0)]);
+        let monomino = SimplePolyomino::new(vec![SimplePoint::new(0, 0)]);
+        domino.set_name(Some('D'));
+
+        let mut b = Board::new(3, 1);
+        b.add_polyomino(&domino, &SimplePoint::new(0, 0));
+        b.add_polyomino(&monomino, &SimplePoint::new(2, 0));
+        assert_eq!(b.to_string(), "+-+-+-+\n|D D|X|\n+-+-+-+\n");
+    }
+
     #[test]
     fn test_point_ordering_for_fit() {
         // Fit does require a particular point ordering, so let's test that here.
@@ -516,6 +902,175 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_read_errors() {
+        type B<'a> = Board<'a, SimplePolyomino<SimplePoint>>;
+
+        match B::from_string("XXX\nX-X\n") {
+            Err(ParseError::InvalidCharacter { line: 2, column: 2, found: '-' }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match B::from_string("  \n\n") {
+            Err(ParseError::Empty) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match B::from_file("data/no-such.board") {
+            Err(ParseError::Io(_)) => (),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_read_old_style() {
+        type B<'a> = Board<'a, SimplePolyomino<SimplePoint>>;
+
+        // Old boards drawn with '#' or 'X' read as they always did, with the
+        // spaces and the ends of short lines left out
+        let old = "########\n## ## ##\n ###\n";
+        let b = B::from_string(old).unwrap();
+        assert_eq!((b.get_width(), b.get_height()), (8, 3));
+        for (y, line) in old.lines().enumerate() {
+            for x in 0..8 {
+                let expected = match line.chars().nth(x) {
+                    Some(c) if c != ' ' => BoardState::Empty,
+                    _ => BoardState::Void,
+                };
+                assert_eq!(b.get(x as i16, y as i16), expected);
+            }
+        }
+
+        // Other characters have to be redrawn as 'X'
+        let starred = "*o*\n* .\n";
+        assert!(B::from_string(starred).is_err());
+        let redrawn: String = starred.chars().map(|c| if c == ' ' || c == '\n' { c } else { 'X' }).collect();
+        assert_eq!(board_utils::count_unoccupied(&B::from_string(&redrawn).unwrap()), 5);
+    }
+
+    #[test]
+    fn test_read_fixed() {
+        type B<'a> = Board<'a, SimplePolyomino<SimplePoint>>;
+
+        let b = B::from_string("XXaa\nbaaX\n").unwrap();
+        assert_eq!(b.get(2, 0), BoardState::Fixed('a'));
+        assert_eq!(b.get(0, 1), BoardState::Fixed('b'));
+        assert_eq!(b.get(3, 1), BoardState::Empty);
+        assert_eq!(board_utils::count_unoccupied(&b), 3);
+
+        let pieces = b.fixed_pieces();
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[0], ('b', vec![SimplePoint::new(0, 1)]));
+        assert_eq!(pieces[1].1.len(), 4);
+
+        // The S tetromino matches the fixed 'a' cells, but nothing matches 'b'
+        let s = SimplePolyomino::new(vec![SimplePoint::new(0, 0), SimplePoint::new(1, 0), SimplePoint::new(1, 1), SimplePoint::new(2, 1)]);
+        let candidates = vec![vec![build_l()], s.make_all_variations()];
+        assert_eq!(board_utils::fixed_candidates(&b, &candidates), vec![1]);
+
+        match B::from_string("aXa\n") {
+            Err(ParseError::DisconnectedPiece { line: 1, column: 3 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_read_layers() {
+        type B<'a> = Board<'a, SimplePolyomino<CubePoint>>;
+
+        let b = B::from_string("XX\nX\n--\n X\nXX\n").unwrap();
+        assert_eq!((b.get_width(), b.get_height(), b.get_depth()), (2, 2, 2));
+        assert_eq!(b.get_3d(1, 0, 0), BoardState::Empty);
+        assert_eq!(b.get_3d(1, 1, 0), BoardState::Void);
+        assert_eq!(b.get_3d(0, 0, 1), BoardState::Void);
+        assert_eq!(b.get_3d(0, 1, 1), BoardState::Empty);
+        assert_eq!(b.get_3d(0, 1, 2), BoardState::Void);
+        assert_eq!(b.to_string(), "..\n.\n-\n .\n..\n");
+        assert_eq!(board_utils::get_first_unoccupied(&b), Some(SimplePoint::new(0, 0)));
+
+        let mut b = B::new_box(3, 2, 4);
+        assert_eq!(b.get_depth(), 4);
+        assert_eq!(b.to_string(), "...\n...\n-\n".repeat(3) + "...\n...\n");
+
+        // A piece fills cells in each layer it reaches
+        let corner = SimplePolyomino::new(vec![CubePoint::new_3d(0, 0, 0), CubePoint::new_3d(0, 0, 1), CubePoint::new_3d(1, 0, 1)]);
+        assert!(b.add_polyomino(&corner, &SimplePoint::new_3d(1, 1, 2)));
+        assert_eq!(b.get_3d(1, 1, 2), BoardState::Full(&corner, 0, 1, 1, 2));
+        assert_eq!(b.get_3d(2, 1, 3), BoardState::Full(&corner, 2, 1, 1, 2));
+        assert!(!b.add_polyomino(&corner, &SimplePoint::new_3d(1, 1, 3)));
+        b.remove_polyomino(&SimplePoint::new_3d(2, 1, 3));
+        assert_eq!(board_utils::count_unoccupied(&b), 24);
+
+        // Layers can be as wide as they like
+        let wide = B::from_string(&format!("X\n-\n{}\n", "X".repeat(40))).unwrap();
+        assert_eq!((wide.get_width(), wide.get_depth()), (40, 2));
+        assert_eq!(board_utils::count_unoccupied(&wide), 41);
+        assert_eq!(board_utils::count_unoccupied(&B::new_box(20, 1, 2)), 40);
+
+        match B::from_string("XX\n-\nX*\n") {
+            Err(ParseError::InvalidCharacter { line: 3, column: 2, found: '*' }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match B::from_string("XX\n-\nXX\n-\naX\nXa\n") {
+            Err(ParseError::DisconnectedPiece { line: 6, column: 2 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+
+        // A pre-placed piece can reach from one layer to the next
+        let b = B::from_string("aX\n-\naX\n").unwrap();
+        assert_eq!(b.fixed_pieces(), vec![('a', vec![SimplePoint::new(0, 0), SimplePoint::new_3d(0, 0, 1)])]);
+    }
+
+    #[test]
+    fn test_read_labels() {
+        type B<'a> = Board<'a, SimplePolyomino<SimplePoint>>;
+
+        let b = B::from_string("XX X\n X\n\nJan - Mar\nSun\n").unwrap();
+        assert_eq!(b.label(0, 0), Some("Jan"));
+        assert_eq!(b.label(1, 0), None);
+        assert_eq!(b.label(3, 0), Some("Mar"));
+        assert_eq!(b.label(1, 1), Some("Sun"));
+        assert_eq!(b.label(5, 5), None);
+        assert_eq!(b.labeled("Sun"), vec![SimplePoint::new(1, 1)]);
+
+        match B::from_string("XX X\n X\n\nJan Feb\nSun\n") {
+            Err(ParseError::LabelCount { line: 4, cells: 3, found: 2 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match B::from_string("XX\n\nJan Feb\nSun\n") {
+            Err(ParseError::LabelCount { line: 4, cells: 0, found: 1 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_reserve() {
+        type B<'a> = Board<'a, SimplePolyomino<SimplePoint>>;
+
+        let mut b = B::from_string("X.X\n").unwrap();
+        assert_eq!(b.get(1, 0), BoardState::Reserved);
+        assert_eq!(board_utils::count_unoccupied(&b), 2);
+
+        assert!(b.reserve(&SimplePoint::new(0, 0)));
+        assert!(!b.reserve(&SimplePoint::new(1, 0)));
+        assert_eq!(b.to_string(), "+-+-+-+\n|. .|.|\n+-+-+-+\n");
+        assert_eq!(board_utils::get_first_unoccupied(&b), Some(SimplePoint::new(2, 0)));
+
+        b.release(&SimplePoint::new(0, 0));
+        b.release(&SimplePoint::new(1, 0));
+        assert_eq!(board_utils::count_unoccupied(&b), 3);
+    }
+
+    #[test]
+    fn test_fix_polyomino() {
+        let i = build_i();
+        let mut b = Board::new(6, 2);
+
+        assert!(b.fix_polyomino(&i, &SimplePoint::new(0, 0), 'i'));
+        assert!(!b.fix_polyomino(&i, &SimplePoint::new(1, 0), 'j'));
+        assert_eq!(b.get(4, 0), BoardState::Fixed('i'));
+        assert_eq!(b.get(5, 0), BoardState::Empty);
+        assert_eq!(board_utils::fixed_candidates(&b, &[i.make_all_variations()]), vec![0]);
+    }
+
     #[test]
     fn test_get_adjacent() {
         // X.X
@@ -537,22 +1092,32 @@ mod tests {
 
         assert_eq!(board_utils::get_adjacent(SimplePoint::new(2, 1), &b).len(), 1);
     }
-}
-
-
-
-
-
-
-
-
-
-
-
-
-
 
+    #[test]
+    fn test_get_region_sizes() {
+        // .X.
+        // .X.
+        // LL.
+        // ..X
+        let l = SimplePolyomino::new(vec![SimplePoint::new(0, 0), SimplePoint::new(1, 0)]);
+        let mut b = Board::<SimplePolyomino<SimplePoint>>::new(3, 4);
 
+        b.set(1, 0, BoardState::Void);
+        b.set(1, 1, BoardState::Void);
+        b.set(2, 3, BoardState::Void);
+        b.add_polyomino(&l, &SimplePoint::new(0, 2));
 
+        let mut scanner = board_utils::RegionScanner::new();
+        let starts = || [(0, 0), (0, 1), (1, 1), (2, 2), (0, 3), (1, 3)].into_iter().map(|(x, y)| SimplePoint::new(x, y));
 
+        let mut sizes = Vec::new();
+        scanner.get_region_sizes(&b, starts(), 10, &mut sizes);
+        sizes.sort();
+        assert_eq!(sizes, vec![2, 2, 3]);
 
+        let mut sizes = Vec::new();
+        scanner.get_region_sizes(&b, starts(), 2, &mut sizes);
+        assert_eq!(sizes, vec![2, 2]);
+        assert_eq!(board_utils::count_unoccupied(&b), 7);
+    }
+}
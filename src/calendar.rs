@@ -0,0 +1,202 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::board::board_utils;
+use crate::board::Board;
+use crate::polyomino::Polyomino;
+use crate::solver::Solver;
+
+/// The labels of the month cells, from January to December.
+pub const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// The labels of the weekday cells, from Monday to Sunday.
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// A day of the year. Months and days are counted from 1, and weekdays from 0
+/// for Monday.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Date {
+    pub month: u8,
+    pub day: u8,
+    pub weekday: Option<u8>,
+}
+
+impl Date {
+    /// The labels of the cells that are left uncovered on this date: the
+    /// month, the day, such as "15", and the weekday if there is one.
+    pub fn labels(&self) -> Vec<String> {
+        let mut res = vec![MONTHS[self.month as usize - 1].to_string(), self.day.to_string()];
+        if let Some(weekday) = self.weekday {
+            res.push(WEEKDAYS[weekday as usize].to_string());
+        }
+
+        res
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(weekday) = self.weekday {
+            write!(f, "{} ", WEEKDAYS[weekday as usize])?;
+        }
+
+        write!(f, "{} {}", MONTHS[self.month as usize - 1], self.day)
+    }
+}
+
+/// Every date of `year`, with its weekday. Without a year there are no
+/// weekdays and 29 February is included, for puzzles that don't show them.
+pub fn dates(year: Option<i32>) -> Vec<Date> {
+    let leap = year.is_none_or(|y| (y % 4 == 0 && y % 100 != 0) || y % 400 == 0);
+    let lengths = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+    let mut res = Vec::new();
+    for (m, &length) in lengths.iter().enumerate() {
+        for day in 1..=length {
+            let month = m as u8 + 1;
+            res.push(Date { month, day, weekday: year.map(|y| weekday(y, month, day)) });
+        }
+    }
+
+    res
+}
+
+// Monday is 0. Sakamoto's method, which counts January and February as part
+// of the year before.
+fn weekday(year: i32, month: u8, day: u8) -> u8 {
+    const OFFSETS: [i32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+
+    let y = if month < 3 { year - 1 } else { year };
+    let sunday_first = (y + y / 4 - y / 100 + y / 400 + OFFSETS[month as usize - 1] + day as i32).rem_euclid(7);
+
+    ((sunday_first + 6) % 7) as u8
+}
+
+/// Why a date can't be set up on a board.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CalendarError {
+    /// No cell of the board has this label.
+    UnknownLabel(String),
+    /// The pieces don't have the same area as the cells left to cover.
+    WrongArea { cells: usize, pieces: usize },
+}
+
+impl fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CalendarError::UnknownLabel(label) => write!(f, "no cell is labeled '{}'", label),
+            CalendarError::WrongArea { cells, pieces } =>
+                write!(f, "the pieces cover {} cells, but {} are left to cover", pieces, cells),
+        }
+    }
+}
+
+impl Error for CalendarError {}
+
+/// Reserves every cell labeled with one of `labels`, so that a solver finds
+/// the arrangements of the pieces that leave exactly those cells uncovered.
+/// The rest of the board must have the same area as the pieces that aren't
+/// already on it.
+pub fn leave_uncovered<P: Polyomino, S: AsRef<str>>(board: &mut Board<P>, candidates: &[Vec<P>],
+                                                     labels: &[S]) -> Result<(), CalendarError> {
+    for label in labels {
+        let cells = board.labeled(label.as_ref());
+        if cells.is_empty() {
+            return Err(CalendarError::UnknownLabel(label.as_ref().to_string()));
+        }
+
+        for cell in cells {
+            board.reserve(&cell);
+        }
+    }
+
+    let fixed = board_utils::fixed_candidates(board, candidates);
+    let pieces = candidates.iter()
+        .enumerate()
+        .filter(|(i, _)| !fixed.contains(i))
+        .map(|(_, vs)| vs.first().map_or(0, |p| p.iter().count()))
+        .sum();
+    let cells = board_utils::count_unoccupied(board);
+
+    if pieces != cells {
+        return Err(CalendarError::WrongArea { cells, pieces });
+    }
+
+    Ok(())
+}
+
+/// The number of solutions for each date of `year`, as given by `dates`.
+/// `setup` is called on each solver before it runs, to set the number of
+/// threads or the strategy.
+pub fn count_dates<'a, P: Polyomino>(board: &Board<'a, P>, candidates: &'a [Vec<P>], year: Option<i32>,
+                                     setup: impl Fn(&mut Solver<'a, '_, P>)) -> Result<Vec<(Date, u32)>, CalendarError> {
+    let mut res = Vec::new();
+
+    for date in dates(year) {
+        let mut b = board.clone();
+        leave_uncovered(&mut b, candidates, &date.labels())?;
+
+        let mut solver = Solver::new(&mut b, candidates);
+        setup(&mut solver);
+        res.push((date, solver.count_solutions()));
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::board::BoardState;
+    use crate::calendar;
+    use crate::calendar::CalendarError;
+    use crate::calendar::Date;
+    use crate::point::SimplePoint;
+    use crate::polyomino::SimplePolyomino;
+    use crate::solver::Solver;
+    use crate::utils;
+    use crate::utils::Restrictions;
+
+    type Poly = SimplePolyomino<SimplePoint>;
+
+    #[test]
+    fn dates() {
+        assert_eq!(calendar::dates(None).len(), 366);
+        assert_eq!(calendar::dates(Some(2023)).len(), 365);
+        assert_eq!(calendar::dates(Some(2024)).len(), 366);
+
+        // 1 January 2024 was a Monday, and 18 October 2026 is a Sunday
+        let dates = calendar::dates(Some(2026));
+        assert_eq!(calendar::dates(Some(2024))[0].weekday, Some(0));
+        assert_eq!(dates[31 + 28 + 31 + 30 + 31 + 30 + 31 + 31 + 30 + 17], Date { month: 10, day: 18, weekday: Some(6) });
+        assert_eq!(dates[0].labels(), vec!["Jan", "1", "Thu"]);
+        assert_eq!(dates[0].to_string(), "Thu Jan 1");
+    }
+
+    #[test]
+    fn calendar_puzzle() {
+        let polys: Vec<Poly> = utils::read_polyominoes_from_file("data/calendar.poly").unwrap();
+        let polys = utils::build_variations(&polys, Restrictions::None);
+        let board = Board::from_file("data/calendar.board").unwrap();
+
+        let mut b = board.clone();
+        calendar::leave_uncovered(&mut b, &polys, &["Oct", "18"]).unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+        let solutions = solver.solve();
+
+        assert!(!solutions.is_empty());
+        for s in solutions {
+            let uncovered: Vec<Option<&str>> = s.col_range()
+                .flat_map(|x| s.row_range().map(move |y| (x, y)))
+                .filter(|&(x, y)| s.get(x, y) == BoardState::Reserved)
+                .map(|(x, y)| s.label(x, y))
+                .collect();
+            assert_eq!(uncovered, vec![Some("Oct"), Some("18")]);
+        }
+
+        let mut b = board.clone();
+        assert_eq!(calendar::leave_uncovered(&mut b, &polys, &["Oct", "32"]), Err(CalendarError::UnknownLabel("32".to_string())));
+        let mut b = board.clone();
+        assert_eq!(calendar::leave_uncovered(&mut b, &polys, &["Oct"]), Err(CalendarError::WrongArea { cells: 42, pieces: 41 }));
+    }
+}
@@ -0,0 +1,429 @@
+use crate::board::board_utils;
+use crate::board::Board;
+use crate::board::BoardState;
+use crate::placement::Placement;
+use crate::point::Point;
+use crate::point::SimplePoint;
+use crate::polyomino::Polyomino;
+use crate::solver::SolutionCallbackFn;
+
+// Index of the root header in the node arrays. Column headers follow it and
+// the nodes for each row follow the headers.
+const ROOT: usize = 0;
+
+// The sparse, doubly linked exact cover matrix from Knuth's "Dancing Links".
+// There is one column for every candidate (each must be used exactly once) and
+// one for every empty cell on the board (each must be covered exactly once).
+// Columns after the primary ones are left out of the header list, so they may
+// be covered at most once but don't have to be.
+struct Matrix {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    row: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl Matrix {
+    fn new(num_columns: usize, num_primary: usize) -> Matrix {
+        let mut m = Matrix {
+            left: Vec::new(),
+            right: Vec::new(),
+            up: Vec::new(),
+            down: Vec::new(),
+            column: Vec::new(),
+            row: Vec::new(),
+            size: vec![0; num_columns + 1],
+        };
+
+        for i in 0..=num_columns {
+            if i > num_primary {
+                m.left.push(i);
+                m.right.push(i);
+            } else {
+                m.left.push(if i == 0 { num_primary } else { i - 1 });
+                m.right.push(if i == num_primary { ROOT } else { i + 1 });
+            }
+            m.up.push(i);
+            m.down.push(i);
+            m.column.push(i);
+            m.row.push(usize::MAX);
+        }
+
+        m
+    }
+
+    fn add_row(&mut self, row: usize, columns: &[usize]) {
+        let first = self.left.len();
+
+        for (n, &c) in columns.iter().enumerate() {
+            let node = first + n;
+
+            self.left.push(if n == 0 { first + columns.len() - 1 } else { node - 1 });
+            self.right.push(if n == columns.len() - 1 { first } else { node + 1 });
+            self.up.push(self.up[c]);
+            self.down.push(c);
+            self.column.push(c);
+            self.row.push(row);
+
+            let last = self.up[c];
+            self.down[last] = node;
+            self.up[c] = node;
+            self.size[c] += 1;
+        }
+    }
+
+    fn cover(&mut self, c: usize) {
+        self.right[self.left[c]] = self.right[c];
+        self.left[self.right[c]] = self.left[c];
+
+        let mut i = self.down[c];
+        while i != c {
+            let mut j = self.right[i];
+            while j != i {
+                self.down[self.up[j]] = self.down[j];
+                self.up[self.down[j]] = self.up[j];
+                self.size[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    fn uncover(&mut self, c: usize) {
+        let mut i = self.up[c];
+        while i != c {
+            let mut j = self.left[i];
+            while j != i {
+                self.size[self.column[j]] += 1;
+                self.down[self.up[j]] = j;
+                self.up[self.down[j]] = j;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+
+        self.right[self.left[c]] = c;
+        self.left[self.right[c]] = c;
+    }
+
+    // The uncovered column with the fewest remaining rows
+    fn choose_column(&self) -> usize {
+        let mut best = self.right[ROOT];
+        let mut c = self.right[best];
+
+        while c != ROOT {
+            if self.size[c] < self.size[best] {
+                best = c;
+            }
+            c = self.right[c];
+        }
+
+        best
+    }
+}
+
+/// An alternative to `Solver` that treats the puzzle as an exact cover problem
+/// and solves it with Knuth's Algorithm X using Dancing Links.
+///
+/// Every candidate must be used exactly once. If the candidates can cover the
+/// board, every empty cell must be covered; if there are too few of them, the
+/// cells they leave empty are allowed, as they are by `Solver`. The solutions
+/// (and their number) are the same as those found by `Solver`, although they
+/// may be produced in a different order.
+pub struct DlxSolver<'a, 'b, P:Polyomino> {
+    board: &'b mut Board<'a, P>,
+    candidates: &'a [Vec<P>],
+    callback_each_solution: Option<&'a SolutionCallbackFn<P>>,
+    solutions: Vec<Board<'a, P>>,
+    enumerate_solutions: bool,
+    exact_fill: bool,
+    num_solutions: u32,
+}
+
+impl<'a, 'b, P:Polyomino> DlxSolver<'a, 'b, P> {
+    pub fn new(b: &'b mut Board<'a, P>, c: &'a [Vec<P>]) -> DlxSolver<'a, 'b, P> {
+        DlxSolver {
+            board: b,
+            candidates: c,
+            callback_each_solution: None,
+            solutions: Vec::new(),
+            enumerate_solutions: false,
+            exact_fill: true,
+            num_solutions: 0,
+        }
+    }
+
+    pub fn set_callback_function(&mut self, cb: &'a SolutionCallbackFn<P>) {
+        self.callback_each_solution = Some(cb);
+    }
+
+    pub fn count_solutions(&mut self) -> u32 {
+        self.run();
+
+        self.num_solutions
+    }
+
+    pub fn solve(&mut self) -> &Vec<Board<'a, P>> {
+        self.enumerate_solutions = true;
+
+        self.run();
+
+        &self.solutions
+    }
+
+    fn run(&mut self) {
+        // Pre-placed pieces are already used
+        let fixed = board_utils::fixed_candidates(self.board, self.candidates);
+        let (mut matrix, placements) = self.build_matrix(&fixed);
+        let mut partial = Vec::new();
+
+        for &i in &fixed {
+            matrix.cover(i + 1);
+        }
+
+        self.search(&mut matrix, &placements, &mut partial);
+    }
+
+    fn build_matrix(&mut self, fixed: &[usize]) -> (Matrix, Vec<Placement>) {
+        let cells: Vec<SimplePoint> = self.board.points().collect();
+
+        // Map each empty cell to its column, following the candidate columns
+        let mut cell_column = vec![None; cells.len()];
+        let mut num_columns = self.candidates.len();
+
+        for pt in &cells {
+            if self.board.get_3d(pt.x, pt.y, pt.z) == BoardState::Empty {
+                num_columns += 1;
+                cell_column[self.board.to_idx(pt.x, pt.y, pt.z)] = Some(num_columns);
+            }
+        }
+
+        // The cells only have to be covered if the pieces left are enough
+        let area: usize = (0..self.candidates.len())
+            .filter(|i| !fixed.contains(i))
+            .map(|i| self.candidates[i].first().map_or(0, |p| p.iter().count()))
+            .sum();
+        self.exact_fill = area >= num_columns - self.candidates.len();
+        let num_primary = if self.exact_fill { num_columns } else { self.candidates.len() };
+
+        let mut matrix = Matrix::new(num_columns, num_primary);
+        let mut placements = Vec::new();
+        let mut columns = Vec::new();
+
+        for (i, variations) in self.candidates.iter().enumerate() {
+            for (v, poly) in variations.iter().enumerate() {
+                // Anchor the piece so that its first cell lands on each cell
+                // in turn, as Solver does
+                let Some(first) = poly.iter().next() else {
+                    continue;
+                };
+
+                for cell in &cells {
+                    let anchor = SimplePoint::new_3d(cell.x - first.x(), cell.y - first.y(), cell.z - first.z());
+                    if !self.board.can_add_polyomino(poly, &anchor) {
+                        continue;
+                    }
+
+                    columns.clear();
+                    columns.push(i + 1);
+                    for pt in poly.iter() {
+                        let idx = self.board.to_idx(pt.x() + anchor.x, pt.y() + anchor.y, pt.z() + anchor.z);
+                        columns.push(cell_column[idx].unwrap());
+                    }
+
+                    matrix.add_row(placements.len(), &columns);
+                    placements.push(Placement {
+                        candidate: i,
+                        variation: v,
+                        anchor,
+                    });
+                }
+            }
+        }
+
+        (matrix, placements)
+    }
+
+    fn search(&mut self, matrix: &mut Matrix, placements: &[Placement], partial: &mut Vec<usize>) {
+        if matrix.right[ROOT] == ROOT {
+            self.record_solution(placements, partial);
+            return;
+        }
+
+        let c = matrix.choose_column();
+        if matrix.size[c] == 0 {
+            return;
+        }
+
+        matrix.cover(c);
+
+        let mut r = matrix.down[c];
+        while r != c {
+            partial.push(matrix.row[r]);
+
+            let mut j = matrix.right[r];
+            while j != r {
+                matrix.cover(matrix.column[j]);
+                j = matrix.right[j];
+            }
+
+            self.search(matrix, placements, partial);
+
+            let mut j = matrix.left[r];
+            while j != r {
+                matrix.uncover(matrix.column[j]);
+                j = matrix.left[j];
+            }
+
+            partial.pop();
+            r = matrix.down[r];
+        }
+
+        matrix.uncover(c);
+    }
+
+    // When the pieces can't cover the board, Solver always covers the first
+    // empty cell in column-major order, so the cells it leaves empty all come
+    // after the first cell of every piece. Other ways of placing the pieces
+    // aren't solutions.
+    fn record_solution(&mut self, placements: &[Placement], partial: &[usize]) {
+        let wanted = self.enumerate_solutions || self.callback_each_solution.is_some();
+        if self.exact_fill && !wanted {
+            self.num_solutions += 1;
+            return;
+        }
+
+        let mut solution = self.board.clone();
+        let mut last_first_cell = None;
+        for &row in partial {
+            let placement = &placements[row];
+            let poly = &self.candidates[placement.candidate][placement.variation];
+            solution.add_polyomino(poly, &placement.anchor);
+
+            let anchor = placement.anchor;
+            let first = poly.iter().next().map(|p| SimplePoint::new_3d(anchor.x + p.x(), anchor.y + p.y(), anchor.z + p.z()));
+            last_first_cell = last_first_cell.max(first);
+        }
+
+        if !self.exact_fill && board_utils::get_first_unoccupied(&solution) < last_first_cell {
+            return;
+        }
+
+        self.num_solutions += 1;
+
+        if let Some(cb) = self.callback_each_solution {
+            cb(&solution)
+        }
+
+        if self.enumerate_solutions {
+            self.solutions.push(solution);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::dlx::DlxSolver;
+    use crate::point::SimplePoint;
+    use crate::point::TrianglePoint;
+    use crate::polyomino::SimplePolyomino;
+    use crate::solver::Solver;
+    use crate::utils;
+    use crate::utils::PredefinedPolyominoes;
+    use crate::utils::Restrictions;
+
+    fn pentominoes() -> Vec<SimplePolyomino<SimplePoint>> {
+        utils::get_polyominoes(PredefinedPolyominoes::Pentominoes).unwrap()
+    }
+
+    #[test]
+    fn count_3x20() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let mut b = Board::new(20, 3);
+        let mut solver = DlxSolver::new(&mut b, &polys);
+
+        assert_eq!(solver.count_solutions(), 8);
+
+        // The board can be used again once the solver is done with it
+        let mut solver = DlxSolver::new(&mut b, &polys);
+        assert_eq!(solver.count_solutions(), 8);
+    }
+
+    #[test]
+    fn same_solutions_as_solver() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        let mut expected: Vec<String> = solver.solve().iter().map(|s| s.to_string()).collect();
+
+        let mut b = Board::new(20, 3);
+        let mut dlx = DlxSolver::new(&mut b, &polys);
+        let mut found: Vec<String> = dlx.solve().iter().map(|s| s.to_string()).collect();
+
+        expected.sort();
+        found.sort();
+        assert_eq!(found.len(), 8);
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn board_with_holes() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let mut b = Board::from_file("data/b8x8holes.board").unwrap();
+        let mut dlx = DlxSolver::new(&mut b, &polys);
+
+        assert_eq!(dlx.count_solutions(), 21 * 8);
+    }
+
+    #[test]
+    fn fixed_piece() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let mut b = Board::from_string("XXXaaaaaXXXXXXXXXXXX\nXXXXXXXXXXXXXXXXXXXX\nXXXXXXXXXXXXXXXXXXXX\n").unwrap();
+        let mut dlx = DlxSolver::new(&mut b, &polys);
+
+        assert_eq!(dlx.count_solutions(), 2);
+    }
+
+    #[test]
+    fn cells_left_empty() {
+        // The tetrominoes cover 20 of the 25 cells, and Solver leaves the rest
+        // empty
+        let polys = utils::build_variations(&utils::get_polyominoes::<SimplePolyomino<SimplePoint>>(PredefinedPolyominoes::Tetrominoes).unwrap(), Restrictions::None);
+
+        let mut b = Board::new(5, 5);
+        let mut solver = Solver::new(&mut b, &polys);
+        let mut expected: Vec<String> = solver.solve().iter().map(|s| s.to_string()).collect();
+
+        let mut b = Board::new(5, 5);
+        let mut dlx = DlxSolver::new(&mut b, &polys);
+        let mut found: Vec<String> = dlx.solve().iter().map(|s| s.to_string()).collect();
+
+        expected.sort();
+        found.sort();
+        assert!(!found.is_empty());
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn hexiamonds() {
+        // Triangles may only move by translations that keep them the right
+        // way up
+        let hexiamonds: Vec<SimplePolyomino<TrianglePoint>> = utils::get_polyominoes(PredefinedPolyominoes::Size6).unwrap();
+        let polys = utils::build_variations(&hexiamonds, Restrictions::None);
+
+        let mut b = Board::from_file("data/hexiamonds-rhombus.board").unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+        let expected = solver.count_solutions();
+
+        let mut b = Board::from_file("data/hexiamonds-rhombus.board").unwrap();
+        let mut dlx = DlxSolver::new(&mut b, &polys);
+
+        assert_eq!(expected, 156 * 4);
+        assert_eq!(dlx.count_solutions(), expected);
+    }
+}
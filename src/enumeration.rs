@@ -0,0 +1,323 @@
+use rustc_hash::FxHashSet;
+
+use crate::point::Point;
+use crate::polyomino::Polyomino;
+
+// Which polyominoes count as the same
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    // Different if they differ in any way, even just by a rotation
+    Fixed,
+    // The same if one can be rotated onto the other
+    OneSided,
+    // The same if one can also be reflected onto the other
+    Free,
+}
+
+// Whether polyominoes that enclose empty cells are included
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Holes {
+    Include,
+    Exclude,
+}
+
+type Cells = Vec<(i16, i16, i16)>;
+
+// Every polyomino with `size` cells on the lattice of P's points, found with
+// Redelmeier's algorithm: polyiamonds on the triangular lattice, polyhexes on
+// the hexagonal one and polycubes on the cubic one. Polycubes that are mirror
+// images of each other are different one-sided polycubes but the same free
+// one.
+//
+// Each polyomino is moved so that its smallest x, y and z are 0, or as near
+// as the lattice allows. One-sided and free polyominoes are given in the
+// orientation whose sorted cells come first. The result is sorted the same
+// way, so it is always in the same order.
+pub fn polyominoes<P: Polyomino>(size: usize, kind: Kind, holes: Holes) -> Vec<P> {
+    let mut found = FxHashSet::default();
+
+    // Cells that can't be moved onto each other, such as triangles that
+    // point up and down, each need a search of their own
+    let origins = if P::Pt::is_translation(1, 0) { &[(0, 0, 0)][..] } else { &[(0, 0, 0), (1, 0, 0)][..] };
+
+    for &origin in origins.iter().filter(|_| size > 0) {
+        let mut search = Search::new(size, origin, is_solid::<P::Pt>());
+        search.extend::<P::Pt>(vec![origin], &mut |cells| {
+            if holes == Holes::Include || !has_hole::<P::Pt>(cells) {
+                found.insert(canonical::<P::Pt>(cells, kind));
+            }
+        });
+    }
+
+    let mut res: Vec<Cells> = found.into_iter().collect();
+    res.sort();
+
+    res.into_iter()
+        .map(|cells| P::new(cells.into_iter().map(|(x, y, z)| P::Pt::new_3d(x, y, z)).collect()))
+        .collect()
+}
+
+// True if cells have neighbours in the layers above and below them
+fn is_solid<T: Point>() -> bool {
+    T::neighbours(0, 0).iter().any(|&(_, _, dz)| dz != 0)
+}
+
+// Redelmeier's algorithm grows polyominoes one cell at a time, from a list of
+// untried cells next to the polyomino so far. A cell that has been tried is
+// never added to the list again further down, so each fixed polyomino is
+// found exactly once. Only cells in the layers above the first one, or above
+// it in its own layer, or to its right on the same row, are used.
+struct Search {
+    size: usize,
+    span: i16,
+    y_span: i16,
+    depth: i16,
+    origin: (i16, i16, i16),
+    seen: Vec<bool>,
+    cells: Cells,
+}
+
+impl Search {
+    fn new(size: usize, origin: (i16, i16, i16), solid: bool) -> Search {
+        // x runs from size - 1 either side of the origin and y from 0 to
+        // size - 1, or from 1 - size in the layers above the first. z runs
+        // from 0 to size - 1 on a solid lattice.
+        let span = size as i16 - 1;
+        let (y_span, depth) = if solid { (span, size as i16) } else { (0, 1) };
+
+        let mut search = Search {
+            size,
+            span,
+            y_span,
+            depth,
+            origin,
+            seen: vec![false; ((2 * span + 1) * (size as i16 + y_span) * depth) as usize],
+            cells: Vec::with_capacity(size),
+        };
+
+        let first = search.idx(origin.0, origin.1, origin.2);
+        search.seen[first] = true;
+        search
+    }
+
+    fn idx(&self, x: i16, y: i16, z: i16) -> usize {
+        let height = self.size as i16 + self.y_span;
+        ((z * height + y + self.y_span) * (2 * self.span + 1) + x - self.origin.0 + self.span) as usize
+    }
+
+    fn allowed(&self, x: i16, y: i16, z: i16) -> bool {
+        (z > 0 || (z == 0 && (y > 0 || (y == 0 && x >= self.origin.0))))
+            && z < self.depth
+            && y < self.size as i16
+            && y >= -self.y_span
+            && (x - self.origin.0).abs() <= self.span
+    }
+
+    fn extend<T: Point>(&mut self, mut untried: Cells, found: &mut impl FnMut(&Cells)) {
+        while let Some((x, y, z)) = untried.pop() {
+            self.cells.push((x, y, z));
+
+            if self.cells.len() == self.size {
+                found(&self.cells);
+            } else {
+                // The new neighbours are only untried further down this branch
+                let mut next = untried.clone();
+                let mut added = Vec::new();
+
+                for &(dx, dy, dz) in T::neighbours(x, y) {
+                    let (nx, ny, nz) = (x + dx, y + dy, z + dz);
+                    if !self.allowed(nx, ny, nz) {
+                        continue;
+                    }
+
+                    let idx = self.idx(nx, ny, nz);
+                    if !self.seen[idx] {
+                        self.seen[idx] = true;
+                        added.push(idx);
+                        next.push((nx, ny, nz));
+                    }
+                }
+
+                self.extend::<T>(next, found);
+
+                for idx in added {
+                    self.seen[idx] = false;
+                }
+            }
+
+            self.cells.pop();
+        }
+    }
+}
+
+// The cells moved as Polyomino::normalize moves them, and sorted
+fn normalize<T: Point>(mut cells: Cells) -> Cells {
+    let min_x = cells.iter().map(|c| c.0).min().unwrap_or(0);
+    let min_y = cells.iter().map(|c| c.1).min().unwrap_or(0);
+    let min_z = cells.iter().map(|c| c.2).min().unwrap_or(0);
+    let (min_x, min_y) = T::origin_offset(min_x, min_y);
+
+    for c in cells.iter_mut() {
+        *c = (c.0 - min_x, c.1 - min_y, c.2 - min_z);
+    }
+
+    cells.sort();
+    cells
+}
+
+// The orientation that comes first of those that count as the same
+fn canonical<T: Point>(cells: &Cells, kind: Kind) -> Cells {
+    let reflections: &[bool] = match kind {
+        Kind::Fixed => return normalize::<T>(cells.clone()),
+        Kind::OneSided => &[false],
+        Kind::Free => &[false, true],
+    };
+
+    let mut best = None;
+
+    for &reflect in reflections {
+        let image: Vec<T> = cells.iter().map(|&(x, y, z)| if reflect { T::new_3d(x, y, z).flip() } else { T::new_3d(x, y, z) }).collect();
+
+        for i in 0..T::LATTICE.rotations() {
+            let candidate = normalize::<T>(image.iter().map(|p| { let p = p.turn(i); (p.x(), p.y(), p.z()) }).collect());
+            if best.as_ref().is_none_or(|b| candidate < *b) {
+                best = Some(candidate);
+            }
+        }
+    }
+
+    best.unwrap_or_default()
+}
+
+// True if some empty cell can't reach the outside of the bounding box without
+// crossing the polyomino
+fn has_hole<T: Point>(cells: &Cells) -> bool {
+    let cells = normalize::<T>(cells.clone());
+    let layers = if is_solid::<T>() { 1 } else { 0 };
+    let width = cells.iter().map(|c| c.0).max().unwrap_or(0) + 5;
+    let height = cells.iter().map(|c| c.1).max().unwrap_or(0) + 5;
+    let depth = cells.iter().map(|c| c.2).max().unwrap_or(0) + 2 * layers + 1;
+    let idx = |x: i16, y: i16, z: i16| ((z * width + x) * height + y) as usize;
+
+    // Flood the empty cells from the corner of a border two cells wide, which
+    // is joined up all the way round on any of the lattices. On the cubic
+    // lattice there is an empty layer above and below as well.
+    let mut filled = vec![false; (width * height * depth) as usize];
+    for &(x, y, z) in &cells {
+        filled[idx(x + 2, y + 2, z + layers)] = true;
+    }
+
+    let mut pending = vec![(0, 0, 0)];
+    filled[0] = true;
+    let mut reached = 1;
+
+    while let Some((x, y, z)) = pending.pop() {
+        for &(dx, dy, dz) in T::neighbours(x, y) {
+            let (nx, ny, nz) = (x + dx, y + dy, z + dz);
+            if nx >= 0 && ny >= 0 && nz >= 0 && nx < width && ny < height && nz < depth && !filled[idx(nx, ny, nz)] {
+                filled[idx(nx, ny, nz)] = true;
+                pending.push((nx, ny, nz));
+                reached += 1;
+            }
+        }
+    }
+
+    reached + cells.len() < (width * height * depth) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enumeration;
+    use crate::enumeration::Holes;
+    use crate::enumeration::Kind;
+    use crate::point::CubePoint;
+    use crate::point::HexPoint;
+    use crate::point::SimplePoint;
+    use crate::point::TrianglePoint;
+    use crate::polyomino::SimplePolyomino;
+
+    fn count(size: usize, kind: Kind, holes: Holes) -> usize {
+        enumeration::polyominoes::<SimplePolyomino<SimplePoint>>(size, kind, holes).len()
+    }
+
+    #[test]
+    fn free() {
+        let counts: Vec<usize> = (1..=8).map(|n| count(n, Kind::Free, Holes::Include)).collect();
+
+        assert_eq!(counts, vec![1, 1, 2, 5, 12, 35, 108, 369]);
+    }
+
+    #[test]
+    fn without_holes() {
+        assert_eq!(count(6, Kind::Free, Holes::Exclude), 35);
+        assert_eq!(count(7, Kind::Free, Holes::Exclude), 107);
+        assert_eq!(count(8, Kind::Free, Holes::Exclude), 363);
+    }
+
+    #[test]
+    fn fixed_and_one_sided() {
+        let fixed: Vec<usize> = (1..=7).map(|n| count(n, Kind::Fixed, Holes::Include)).collect();
+        let one_sided: Vec<usize> = (1..=7).map(|n| count(n, Kind::OneSided, Holes::Include)).collect();
+
+        assert_eq!(fixed, vec![1, 2, 6, 19, 63, 216, 760]);
+        assert_eq!(one_sided, vec![1, 1, 2, 7, 18, 60, 196]);
+    }
+
+    #[test]
+    fn polyiamonds() {
+        let count = |size, kind| enumeration::polyominoes::<SimplePolyomino<TrianglePoint>>(size, kind, Holes::Include).len();
+
+        let free: Vec<usize> = (1..=8).map(|n| count(n, Kind::Free)).collect();
+        let one_sided: Vec<usize> = (1..=8).map(|n| count(n, Kind::OneSided)).collect();
+        let fixed: Vec<usize> = (1..=8).map(|n| count(n, Kind::Fixed)).collect();
+
+        assert_eq!(free, vec![1, 1, 1, 3, 4, 12, 24, 66]);
+        assert_eq!(one_sided, vec![1, 1, 1, 4, 6, 19, 43, 120]);
+        assert_eq!(fixed, vec![2, 3, 6, 14, 36, 94, 250, 675]);
+
+        // The smallest polyiamonds with a hole have nine cells, and only one
+        // of the 160 goes all the way round an empty triangle
+        let without_holes = enumeration::polyominoes::<SimplePolyomino<TrianglePoint>>(9, Kind::Free, Holes::Exclude).len();
+        assert_eq!(without_holes, 160 - 1);
+    }
+
+    #[test]
+    fn polyhexes() {
+        let count = |size, kind, holes| enumeration::polyominoes::<SimplePolyomino<HexPoint>>(size, kind, holes).len();
+
+        let free: Vec<usize> = (1..=6).map(|n| count(n, Kind::Free, Holes::Include)).collect();
+        let one_sided: Vec<usize> = (1..=6).map(|n| count(n, Kind::OneSided, Holes::Include)).collect();
+        let fixed: Vec<usize> = (1..=6).map(|n| count(n, Kind::Fixed, Holes::Include)).collect();
+
+        assert_eq!(free, vec![1, 1, 3, 7, 22, 82]);
+        assert_eq!(one_sided, vec![1, 1, 3, 10, 33, 147]);
+        assert_eq!(fixed, vec![1, 3, 11, 44, 186, 814]);
+
+        // The ring of six around an empty hexagon is the smallest with a hole
+        assert_eq!(count(6, Kind::Free, Holes::Exclude), 82 - 1);
+    }
+
+    #[test]
+    fn polycubes() {
+        let count = |size, kind, holes| enumeration::polyominoes::<SimplePolyomino<CubePoint>>(size, kind, holes).len();
+
+        let free: Vec<usize> = (1..=6).map(|n| count(n, Kind::Free, Holes::Include)).collect();
+        let one_sided: Vec<usize> = (1..=6).map(|n| count(n, Kind::OneSided, Holes::Include)).collect();
+        let fixed: Vec<usize> = (1..=6).map(|n| count(n, Kind::Fixed, Holes::Include)).collect();
+
+        assert_eq!(free, vec![1, 1, 2, 7, 23, 112]);
+        assert_eq!(one_sided, vec![1, 1, 2, 8, 29, 166]);
+        assert_eq!(fixed, vec![1, 3, 15, 86, 534, 3481]);
+
+        // A flat ring of eight doesn't enclose the cell in its middle, which
+        // is open above and below
+        let ring = vec![(0, 0, 0), (0, 1, 0), (0, 2, 0), (1, 0, 0), (1, 2, 0), (2, 0, 0), (2, 1, 0), (2, 2, 0)];
+        assert!(enumeration::has_hole::<SimplePoint>(&ring));
+        assert!(!enumeration::has_hole::<CubePoint>(&ring));
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(count(0, Kind::Free, Holes::Include), 0);
+    }
+}
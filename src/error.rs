@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// A problem with a board or pieces file. Lines and columns count from 1.
+#[derive(Debug)]
+pub enum ParseError {
+    Io(io::Error),
+    InvalidCharacter { line: usize, column: usize, found: char },
+    // There are no cells at all
+    Empty,
+    DisconnectedPiece { line: usize, column: usize },
+    // The same piece, up to rotation and reflection, as the one on first_line
+    DuplicatePiece { line: usize, column: usize, first_line: usize },
+    // A cell drawn with a different letter from the rest of its piece
+    MixedNames { line: usize, column: usize },
+    // The same name as the piece on first_line
+    DuplicateName { line: usize, column: usize, first_line: usize },
+    // The labels on line don't match the number of cells in their row
+    LabelCount { line: usize, cells: usize, found: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "{}", e),
+            ParseError::InvalidCharacter { line, column, found } =>
+                write!(f, "line {}, column {}: invalid character {:?}", line, column, found),
+            ParseError::Empty => write!(f, "no cells found"),
+            ParseError::DisconnectedPiece { line, column } =>
+                write!(f, "line {}, column {}: cell is not joined to the rest of its piece", line, column),
+            ParseError::DuplicatePiece { line, column, first_line } =>
+                write!(f, "line {}, column {}: piece is the same as the one on line {}", line, column, first_line),
+            ParseError::MixedNames { line, column } =>
+                write!(f, "line {}, column {}: cell is drawn with a different letter from the rest of its piece", line, column),
+            ParseError::DuplicateName { line, column, first_line } =>
+                write!(f, "line {}, column {}: piece has the same name as the one on line {}", line, column, first_line),
+            ParseError::LabelCount { line, cells, found } =>
+                write!(f, "line {}: {} labels for a row of {} cells", line, found, cells),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> ParseError {
+        ParseError::Io(e)
+    }
+}
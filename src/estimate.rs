@@ -0,0 +1,139 @@
+use std::fmt;
+
+/// The number that a 95% confidence interval reaches either side of the mean,
+/// in standard errors.
+const Z_95: f64 = 1.96;
+
+/// A value estimated from random samples, with a 95% confidence interval.
+/// The interval comes from the normal approximation, and never goes below 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub value: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Interval {
+    /// The mean of the samples, and the interval the true mean is likely to
+    /// be in.
+    pub fn from_samples(samples: &[f64]) -> Interval {
+        let n = samples.len() as f64;
+        if samples.is_empty() {
+            return Interval { value: 0.0, low: 0.0, high: 0.0 };
+        }
+
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = if samples.len() > 1 {
+            samples.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        let margin = Z_95 * (variance / n).sqrt();
+
+        Interval { value: mean, low: (mean - margin).max(0.0), high: mean + margin }
+    }
+}
+
+/// How big a search is, as estimated by `Solver::estimate` with Knuth's
+/// method. Each probe goes down the search tree from the top, taking a random
+/// branch at each step, and multiplies out the number of branches it saw on
+/// the way. Averaged over many probes this gives the size of the tree.
+///
+/// Every probe is an unbiased estimate, but now and then one finds a much
+/// bigger part of the tree than the rest, so the intervals are only a guide
+/// until there have been enough probes for them to settle down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub probes: usize,
+    /// The number of positions the search goes through, counting the one it
+    /// starts from.
+    pub nodes: Interval,
+    /// The number of solutions, before any are left out by
+    /// `Solver::set_distinct`.
+    pub solutions: Interval,
+    /// How long a search with one thread would take, from the time the
+    /// probes spent on each position multiplied out the same way.
+    pub seconds: Interval,
+}
+
+impl Estimate {
+    /// The estimate from the nodes, solutions and seconds found by each probe.
+    pub fn new(nodes: &[f64], solutions: &[f64], seconds: &[f64]) -> Estimate {
+        Estimate {
+            probes: nodes.len(),
+            nodes: Interval::from_samples(nodes),
+            solutions: Interval::from_samples(solutions),
+            seconds: Interval::from_samples(seconds),
+        }
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let interval = |i: &Interval, show: fn(f64) -> String| {
+            format!("{} (95% between {} and {})", show(i.value), show(i.low), show(i.high))
+        };
+
+        writeln!(f, "Probes: {}", self.probes)?;
+        writeln!(f, "Nodes: {}", interval(&self.nodes, count))?;
+        writeln!(f, "Solutions: {}", interval(&self.solutions, count))?;
+        writeln!(f, "Time: {}", interval(&self.seconds, duration))
+    }
+}
+
+// Whole numbers up to a million, and anything bigger in scientific notation
+fn count(n: f64) -> String {
+    if n < 1e6 {
+        format!("{:.0}", n)
+    } else {
+        format!("{:.2e}", n)
+    }
+}
+
+// A time in the largest unit that keeps it at 1 or more
+fn duration(seconds: f64) -> String {
+    const UNITS: [(f64, &str); 5] = [
+        (365.25 * 24.0 * 3600.0, "years"),
+        (24.0 * 3600.0, "days"),
+        (3600.0, "hours"),
+        (60.0, "minutes"),
+        (1.0, "s"),
+    ];
+
+    match UNITS.iter().find(|&&(length, _)| seconds >= length) {
+        Some(&(length, name)) if seconds / length >= 1e6 => format!("{} {}", count(seconds / length), name),
+        Some(&(length, name)) => format!("{:.1} {}", seconds / length, name),
+        None => format!("{:.3} s", seconds),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::estimate::Estimate;
+    use crate::estimate::Interval;
+
+    #[test]
+    fn intervals() {
+        let i = Interval::from_samples(&[2.0, 4.0, 6.0, 8.0]);
+        assert_eq!(i.value, 5.0);
+        assert!((i.high - i.value - 1.96 * (20.0f64 / 3.0 / 4.0).sqrt()).abs() < 1e-9);
+        assert_eq!(i.value - i.low, i.high - i.value);
+
+        // The low end stops at 0, and one sample says nothing about the spread
+        assert_eq!(Interval::from_samples(&[0.0, 0.0, 0.0, 100.0]).low, 0.0);
+        assert_eq!(Interval::from_samples(&[7.0]), Interval { value: 7.0, low: 7.0, high: 7.0 });
+        assert_eq!(Interval::from_samples(&[]).value, 0.0);
+    }
+
+    #[test]
+    fn display() {
+        let estimate = Estimate::new(&[3e9, 3e9], &[12.0, 12.0], &[3000.0, 3000.0]);
+        assert_eq!(estimate.to_string(), "Probes: 2\n\
+                                          Nodes: 3.00e9 (95% between 3.00e9 and 3.00e9)\n\
+                                          Solutions: 12 (95% between 12 and 12)\n\
+                                          Time: 50.0 minutes (95% between 50.0 minutes and 50.0 minutes)\n");
+
+        let estimate = Estimate::new(&[1.0], &[0.0], &[1e-7]);
+        assert!(estimate.to_string().ends_with("Time: 0.000 s (95% between 0.000 s and 0.000 s)\n"));
+    }
+}
@@ -1,5 +1,15 @@
 pub mod board;
+pub mod calendar;
+pub mod dlx;
+pub mod enumeration;
+pub mod error;
+pub mod estimate;
+pub mod placement;
 pub mod point;
 pub mod polyomino;
 pub mod solver;
+pub mod svg;
+pub mod symmetry;
+pub mod terminal;
 pub mod utils;
+pub mod verify;
@@ -1,73 +1,719 @@
-use std::time::Instant;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::IsTerminal;
+use std::mem;
+use std::path::Path;
+use std::process::ExitCode;
 
 use polyomino::board::Board;
+use polyomino::board::BoardState;
+use polyomino::calendar;
+use polyomino::calendar::Date;
+use polyomino::placement;
+use polyomino::placement::PlacementError;
+use polyomino::point::CubePoint;
+use polyomino::point::HexPoint;
+use polyomino::point::Lattice;
+use polyomino::point::Point;
 use polyomino::point::SimplePoint;
+use polyomino::point::TrianglePoint;
 use polyomino::polyomino::Polyomino;
 use polyomino::polyomino::SimplePolyomino;
+use polyomino::solver::Solver;
+use polyomino::solver::Strategy;
+use polyomino::svg;
+use polyomino::terminal;
 use polyomino::utils;
-use polyomino::utils::Restrictions;
+use polyomino::utils::Orientations;
 use polyomino::utils::PredefinedPolyominoes;
-use polyomino::solver::Solver;
+use polyomino::verify::Verifier;
+
+const USAGE: &str = "\
+Usage: polyrun [OPTIONS] <BOARD>
+       polyrun calendar [OPTIONS] <BOARD>
+       polyrun verify [OPTIONS] <BOARD> <SOLUTIONS>
+       polyrun estimate [OPTIONS] <BOARD>
+
+Fits a set of pieces onto a board and prints the solutions. The calendar
+command counts the solutions for every date of the year instead, leaving
+the cells labeled with the month, the day and the weekday (if there is a
+year) uncovered, and reports the dates with no solutions and the fewest.
+The verify command checks solutions made elsewhere instead, and says what
+is wrong with each one that breaks the rules. The estimate command makes
+random probes down the search tree instead of searching it, and estimates
+how many positions the search goes through, how many solutions there are
+and how long counting them with one thread would take.
+
+<BOARD> is a board file, with an X or a # for each cell that has to be
+covered, a . for each cell that has to stay uncovered and another letter
+for each cell of a piece that is already in place, or a size such as 10x6
+for a plain rectangle. A board file can end with a blank line and a line
+of labels for each row, such as Jan or 15, one for each cell. On a
+triangular lattice each character is a triangle, pointing up if its
+column plus its line (both counting from 0) is even. On a hexagonal lattice
+each character is a hexagon, and each line is half a hexagon further right
+than the line above it. On a cubic lattice each character is a cube, the
+layers come one after the other with a line of dashes between them, and a
+size such as 3x3x3 is a box.
+
+<SOLUTIONS> is a file of solutions as printed by --format json, placements
+or letters. In the letters format solutions are separated by blank lines,
+and each group of touching cells with the same letter is a piece.
+
+Options:
+  -p, --pieces <PIECES>      A file of pieces, or one of the sets monominoes,
+                             dominoes, triominoes, tetrominoes, pentominoes,
+                             hexominoes, heptominoes, octominoes, nonominoes
+                             or decominoes [default: pentominoes], or of
+                             the polyiamonds moniamonds up to octiamonds or
+                             the polyhexes monohexes up to hexahexes
+  -m, --mode <MODE>          count, first or all [default: count]
+  -s, --symmetry <SYMMETRY>  none, square or rectangular. Kept for old
+                             scripts: square and rectangular do the same as
+                             --distinct, on any board [default: none]
+      --lattice <LATTICE>    square, triangle, hexagon or cube. Polyiamond and
+                             polyhex sets are on their own lattice without
+                             this [default: square]
+      --orientations <ORIENTATIONS>
+                             rotations, for pieces that can only be turned
+                             round, or all, for pieces that can be turned
+                             over into their mirror images as well. Solid
+                             pieces have 24 rotations and up to 48
+                             orientations in all [default: all, or rotations
+                             on a cubic lattice]
+  -f, --format <FORMAT>      How solutions are printed: boxes, which are drawn
+                             in color on a terminal unless NO_COLOR is set,
+                             ascii for plain boxes, letters for one character
+                             per cell, json for a list of placements of each
+                             solution, placements for the same with a line
+                             such as 3:5@0,2 ... for each solution (piece,
+                             orientation and position), or svg for a picture
+                             with all the solutions on one page
+                             [default: boxes]
+  -d, --distinct             Leave out solutions that are rotations or
+                             reflections of one found before, on any board
+                             and with any number of threads. Counts show how
+                             many there are with them too
+  -t, --threads <THREADS>    Search with this many threads [default: 1]
+      --strategy <STRATEGY>  What the search branches on: first-empty, cell or
+                             piece [default: first-empty]
+      --seed <SEED>          Try the pieces in a random order made from this
+                             number, so that --mode first prints a random
+                             solution, the same one for the same seed and
+                             number of threads [default: none]
+      --probes <PROBES>      How many probes the estimate command makes
+                             [default: 1000]
+  -u, --uncovered <CELLS>    Allow up to this many cells to be left uncovered
+                             [default: 0]
+  -l, --leave <LABELS>       Leave exactly the cells with these labels
+                             uncovered, separated by commas, such as Oct,18
+  -y, --year <YEAR>          The year for the calendar command, which also
+                             leaves the weekday uncovered [default: none]
+  -h, --help                 Print this message
 
-fn main() {
-    let polyomino_name = "data/pentomino.poly";
+Exit status:
+  0  at least one solution was found
+  1  there are no solutions, or a solution given to verify is wrong
+  2  the arguments were not understood
+  3  the board or the pieces could not be read
+";
 
-    if let Ok(polyominoes) = utils::get_polyominoes::<SimplePolyomino<SimplePoint>>(PredefinedPolyominoes::Pentominoes) {
+const NO_SOLUTIONS: u8 = 1;
+const WRONG_SOLUTION: u8 = 1;
+const BAD_ARGUMENTS: u8 = 2;
+const BAD_INPUT: u8 = 3;
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Solve,
+    Calendar,
+    Verify,
+    Estimate,
+}
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Count,
+    First,
+    All,
+}
+
+#[derive(Debug, PartialEq)]
+enum Format {
+    Boxes,
+    Ascii,
+    Letters,
+    Json,
+    Placements,
+    Svg,
+}
+
+struct Options {
+    command: Command,
+    board: String,
+    solutions: String,
+    pieces: String,
+    mode: Mode,
+    distinct: bool,
+    lattice: Option<Lattice>,
+    orientations: Option<Orientations>,
+    format: Format,
+    threads: usize,
+    strategy: Strategy,
+    seed: Option<u64>,
+    probes: usize,
+    uncovered: usize,
+    leave: Vec<String>,
+    year: Option<i32>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(msg) => {
+            eprintln!("polyrun: {}\nTry 'polyrun --help' for more information.", msg);
+            return ExitCode::from(BAD_ARGUMENTS);
+        }
+    };
+
+    match options.lattice.unwrap_or_else(|| PredefinedPolyominoes::lattice(&options.pieces)) {
+        Lattice::Square => run::<SimplePolyomino<SimplePoint>>(options),
+        Lattice::Triangular => run::<SimplePolyomino<TrianglePoint>>(options),
+        Lattice::Hexagonal => run::<SimplePolyomino<HexPoint>>(options),
+        Lattice::Cubic => run::<SimplePolyomino<CubePoint>>(options),
+    }
+}
+
+fn run<P: Polyomino>(options: Options) -> ExitCode {
+    let pieces: Vec<P> = match read_pieces(&options.pieces) {
+        Ok(pieces) => pieces,
+        Err(msg) => {
+            eprintln!("polyrun: {}", msg);
+            return ExitCode::from(BAD_INPUT);
+        }
+    };
+
+    let mut board = match read_board(&options.board) {
+        Ok(board) => board,
+        Err(msg) => {
+            eprintln!("polyrun: {}", msg);
+            return ExitCode::from(BAD_INPUT);
+        }
+    };
+
+    let orientations = options.orientations.unwrap_or(Orientations::default_for(P::Pt::LATTICE));
+    let candidates = utils::build_orientations(&pieces, orientations);
+
+    if options.command == Command::Calendar {
+        let counts = calendar::count_dates(&board, &candidates, options.year, |solver| {
+            solver.set_threads(options.threads);
+            solver.set_strategy(options.strategy);
+        });
+
+        return match counts {
+            Ok(counts) => report_dates(&counts),
+            Err(e) => {
+                eprintln!("polyrun: {}", e);
+                ExitCode::from(BAD_INPUT)
+            }
+        };
+    }
 
-        // Comparing performance of merely counting the number of solutions vs computing
-        // and returning each solution
-        get_number_of_solutions(&polyominoes);
-        
-        get_solutions(&polyominoes);
+    if !options.leave.is_empty() {
+        if let Err(e) = calendar::leave_uncovered(&mut board, &candidates, &options.leave) {
+            eprintln!("polyrun: {}", e);
+            return ExitCode::from(BAD_INPUT);
+        }
+    }
+
+    if options.command == Command::Verify {
+        return verify(&board, &candidates, &options.solutions, options.uncovered);
+    }
+
+    let mut solver = Solver::new(&mut board, &candidates);
+    solver.set_threads(options.threads);
+    solver.set_strategy(options.strategy);
+    solver.set_max_uncovered(options.uncovered);
+    solver.set_distinct(options.distinct);
+    if let Some(seed) = options.seed {
+        solver.set_seed(seed);
+    }
+
+    if options.command == Command::Estimate {
+        print!("{}", solver.estimate(options.probes));
+        return ExitCode::SUCCESS;
+    }
+
+    let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let print = |n: usize, solution: &Board<P>| -> Result<(), PlacementError> {
+        if n > 0 && options.format != Format::Placements {
+            println!();
+        }
+
+        match options.format {
+            Format::Boxes if color => print!("{}", terminal::to_ansi(solution)),
+            Format::Boxes | Format::Ascii => print!("{}", solution),
+            Format::Letters => print!("{}", letters(solution)),
+            Format::Placements => print!("{}", placement::to_text(&[placement::placements(solution, &candidates)?])),
+            Format::Json | Format::Svg => unreachable!(),
+        }
+
+        Ok(())
+    };
+
+    let found = match options.mode {
+        Mode::Count => {
+            let num_solutions = solver.count_solutions();
+            if options.distinct {
+                println!("{} distinct, {} in all", num_solutions, solver.raw_count());
+            } else {
+                println!("{}", num_solutions);
+            }
+            Ok(num_solutions > 0)
+        }
+        Mode::First | Mode::All if matches!(options.format, Format::Json | Format::Svg) => {
+            let solutions: Vec<Board<P>> = match options.mode {
+                Mode::First => solver.solutions().take(1).collect(),
+                _ => solver.solve().clone(),
+            };
+
+            if options.format == Format::Json {
+                let placements = solutions.iter().map(|s| placement::placements(s, &candidates)).collect::<Result<Vec<_>, _>>();
+                placements.map(|placements| {
+                    println!("{}", placement::to_json(&placements));
+                    !solutions.is_empty()
+                })
+            } else {
+                if !solutions.is_empty() {
+                    // As many columns as rows, more or less
+                    let columns = (1..).find(|c| c * c >= solutions.len()).unwrap();
+                    print!("{}", svg::sheet(&solutions, columns));
+                }
+                Ok(!solutions.is_empty())
+            }
+        }
+        Mode::First => {
+            let first = solver.solutions().next();
+            first.iter().try_for_each(|s| print(0, s)).map(|_| first.is_some())
+        }
+        Mode::All if options.threads > 1 => {
+            let solutions = solver.solve();
+            solutions.iter().enumerate().try_for_each(|(n, s)| print(n, s)).map(|_| !solutions.is_empty())
+        }
+        Mode::All => solver.solutions().enumerate().try_fold(false, |_, (n, s)| print(n, &s).map(|_| true)),
+    };
+
+    let found = match found {
+        Ok(found) => found,
+        Err(e) => {
+            eprintln!("polyrun: {}", e);
+            return ExitCode::from(BAD_INPUT);
+        }
+    };
+
+    if found {
+        ExitCode::SUCCESS
     } else {
-        panic!("Can't find polyomino file {}", polyomino_name);
+        if options.mode != Mode::Count {
+            eprintln!("polyrun: no solutions");
+        }
+        ExitCode::from(NO_SOLUTIONS)
+    }
+}
+
+// Returns None if help was asked for
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut board = None;
+    let mut options = Options {
+        command: Command::Solve,
+        board: String::new(),
+        solutions: String::new(),
+        pieces: "pentominoes".to_string(),
+        mode: Mode::Count,
+        distinct: false,
+        lattice: None,
+        orientations: None,
+        format: Format::Boxes,
+        threads: 1,
+        strategy: Strategy::FirstEmptyCell,
+        seed: None,
+        probes: 1000,
+        uncovered: 0,
+        leave: Vec::new(),
+        year: None,
+    };
+
+    let mut args = args.peekable();
+    if args.next_if(|arg| arg == "calendar").is_some() {
+        options.command = Command::Calendar;
+    } else if args.next_if(|arg| arg == "verify").is_some() {
+        options.command = Command::Verify;
+    } else if args.next_if(|arg| arg == "estimate").is_some() {
+        options.command = Command::Estimate;
+    }
+    let mut solutions = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(None);
+        }
+        if arg == "-d" || arg == "--distinct" {
+            options.distinct = true;
+            continue;
+        }
+
+        if !arg.starts_with('-') {
+            if board.is_none() {
+                board = Some(arg);
+            } else if options.command == Command::Verify && solutions.is_none() {
+                solutions = Some(arg);
+            } else {
+                return Err("only one board can be given".to_string());
+            }
+            continue;
+        }
+
+        let Some(value) = args.next() else {
+            return Err(format!("{} needs a value", arg));
+        };
+
+        match arg.as_str() {
+            "-p" | "--pieces" => options.pieces = value,
+            "-m" | "--mode" => {
+                options.mode = match value.as_str() {
+                    "count" => Mode::Count,
+                    "first" => Mode::First,
+                    "all" => Mode::All,
+                    _ => return Err(format!("unknown mode '{}'", value)),
+                }
+            }
+            "-s" | "--symmetry" => {
+                options.distinct |= match value.as_str() {
+                    "none" => false,
+                    "square" | "rectangular" => true,
+                    _ => return Err(format!("unknown symmetry '{}'", value)),
+                }
+            }
+            "--lattice" => {
+                options.lattice = match value.as_str() {
+                    "square" => Some(Lattice::Square),
+                    "triangle" | "triangular" => Some(Lattice::Triangular),
+                    "hexagon" | "hexagonal" => Some(Lattice::Hexagonal),
+                    "cube" | "cubic" => Some(Lattice::Cubic),
+                    _ => return Err(format!("unknown lattice '{}'", value)),
+                }
+            }
+            "--orientations" => {
+                options.orientations = match value.as_str() {
+                    "rotations" => Some(Orientations::Rotations),
+                    "all" => Some(Orientations::All),
+                    _ => return Err(format!("unknown orientations '{}'", value)),
+                }
+            }
+            "-f" | "--format" => {
+                options.format = match value.as_str() {
+                    "boxes" => Format::Boxes,
+                    "ascii" => Format::Ascii,
+                    "letters" => Format::Letters,
+                    "json" => Format::Json,
+                    "placements" => Format::Placements,
+                    "svg" => Format::Svg,
+                    _ => return Err(format!("unknown format '{}'", value)),
+                }
+            }
+            "-t" | "--threads" => {
+                options.threads = match value.parse() {
+                    Ok(threads) if threads > 0 => threads,
+                    _ => return Err(format!("'{}' is not a number of threads", value)),
+                }
+            }
+            "--strategy" => {
+                options.strategy = match value.as_str() {
+                    "first-empty" => Strategy::FirstEmptyCell,
+                    "cell" => Strategy::MostConstrainedCell,
+                    "piece" => Strategy::MostConstrainedPiece,
+                    _ => return Err(format!("unknown strategy '{}'", value)),
+                }
+            }
+            "--seed" => {
+                options.seed = match value.parse() {
+                    Ok(seed) => Some(seed),
+                    _ => return Err(format!("'{}' is not a seed", value)),
+                }
+            }
+            "--probes" => {
+                options.probes = match value.parse() {
+                    Ok(probes) if probes > 0 => probes,
+                    _ => return Err(format!("'{}' is not a number of probes", value)),
+                }
+            }
+            "-u" | "--uncovered" => {
+                options.uncovered = match value.parse() {
+                    Ok(cells) => cells,
+                    _ => return Err(format!("'{}' is not a number of cells", value)),
+                }
+            }
+            "-l" | "--leave" => options.leave = value.split(',').map(String::from).collect(),
+            "-y" | "--year" => {
+                options.year = match value.parse() {
+                    Ok(year) => Some(year),
+                    _ => return Err(format!("'{}' is not a year", value)),
+                }
+            }
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+
+    let Some(board) = board else {
+        return Err("no board was given".to_string());
+    };
+    options.board = board;
+
+    if options.command == Command::Verify {
+        let Some(solutions) = solutions else {
+            return Err("no solutions were given".to_string());
+        };
+        options.solutions = solutions;
     }
+
+    Ok(Some(options))
 }
 
-fn get_solutions<'a> (polyominoes: &[SimplePolyomino<SimplePoint>]) {
-    let all_polyominoes = utils::build_variations(polyominoes, Restrictions::RectangularSymmetry);
-    let mut b = Board::new(10, 6);
-    let mut solver = Solver::new(&mut b, &all_polyominoes);
-    // solver.set_callback_function(&call_back::<(), SimplePoint>);
-    // solver.set_region_checker(&check_region_pentomino::<SimplePolyomino<SimplePoint>>);
-    let start_time = Instant::now();
-    let num_solutions = solver.solve().len();
-    let elapsed = start_time.elapsed();
-    let elapsed_millis = elapsed.as_secs() * 1000_u64 + elapsed.subsec_millis() as u64;
-    
-    println!(
-        "{} solutions found in {}ms ({} solutions/second)",
-        num_solutions,
-        elapsed_millis,
-        ((num_solutions as f64 / elapsed_millis as f64) * 1000.0).round()
-            );
+// A file of pieces if there is one, otherwise the name of a predefined set
+fn read_pieces<P: Polyomino>(name: &str) -> Result<Vec<P>, String> {
+    if Path::new(name).exists() {
+        return utils::read_polyominoes_from_file(name).map_err(|e| format!("can't read {}: {}", name, e));
+    }
+
+    let set: PredefinedPolyominoes = name.parse()?;
+    utils::get_polyominoes(set).map_err(|e| format!("can't make the {}: {}", name, e))
 }
 
-fn get_number_of_solutions<'a> (polyominoes: &[SimplePolyomino<SimplePoint>]) {
-    let all_polyominoes = utils::build_variations(polyominoes, Restrictions::RectangularSymmetry);
-    let mut b = Board::new(10, 6);
-    let mut solver = Solver::new(&mut b, &all_polyominoes);
-    // solver.set_callback_function(&call_back::<(), SimplePoint>);
-    // solver.set_region_checker(&check_region_pentomino::<SimplePolyomino<SimplePoint>>);
-    let start_time = Instant::now();
-    let num_solutions = solver.count_solutions();
-    let elapsed = start_time.elapsed();
-    let elapsed_millis = elapsed.as_secs() * 1000_u64 + elapsed.subsec_millis() as u64;
-    
-    println!(
-        "{} solutions found in {}ms ({} solutions/second)",
-        num_solutions,
-        elapsed_millis,
-        ((num_solutions as f64 / elapsed_millis as f64) * 1000.0).round()
-            );
+// A board file if there is one, otherwise the size of a rectangle, or of a
+// box such as 3x3x3 on the cubic lattice
+fn read_board<P: Polyomino>(name: &str) -> Result<Board<'_, P>, String> {
+    if Path::new(name).exists() {
+        return Board::from_file(name).map_err(|e| format!("can't read {}: {}", name, e));
+    }
+
+    let size: Option<Vec<i16>> = name.split('x').map(|n| n.parse().ok()).collect();
+    match size.as_deref() {
+        Some(&[w, h]) if w > 0 && h > 0 => Ok(Board::new(w, h)),
+        Some(&[w, h, d]) if w > 0 && h > 0 && d > 0 && P::Pt::LATTICE == Lattice::Cubic => Ok(Board::new_box(w, h, d)),
+        _ => Err(format!("{} is neither a board file nor a size such as 10x6", name)),
+    }
 }
 
-#[allow(dead_code)]
-fn call_back<P:Polyomino>(b: &Board<P>) {
-    println!("{}", b)
+// Prints the dates with no solutions and those with the fewest and the most
+fn report_dates(counts: &[(Date, u32)]) -> ExitCode {
+    let dates_with = |n: u32| counts.iter().filter(|c| c.1 == n).map(|c| c.0.to_string()).collect::<Vec<_>>().join(", ");
+    let fewest = counts.iter().map(|c| c.1).filter(|&n| n > 0).min();
+    let most = counts.iter().map(|c| c.1).max().unwrap_or(0);
+    let unsolved = dates_with(0);
+
+    println!("Dates: {}", counts.len());
+    println!("No solutions: {}", if unsolved.is_empty() { "none" } else { &unsolved });
+    if let Some(fewest) = fewest {
+        println!("Fewest solutions: {} ({})", fewest, dates_with(fewest));
+        println!("Most solutions: {} ({})", most, dates_with(most));
+    }
+
+    if unsolved.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(NO_SOLUTIONS)
+    }
 }
 
-#[allow(dead_code)]
-fn check_region_pentomino<P:Polyomino>(_b: &Board<P>, region_size: usize) -> bool {
-    region_size % 5 == 0
+// Checks every solution in the file and prints what is wrong with each
+fn verify<P: Polyomino>(board: &Board<P>, candidates: &[Vec<P>], file: &str, uncovered: usize) -> ExitCode {
+    let contents = match fs::read_to_string(file) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("polyrun: can't read {}: {}", file, e);
+            return ExitCode::from(BAD_INPUT);
+        }
+    };
+
+    let mut verifier = Verifier::new(board, candidates);
+    verifier.set_max_uncovered(uncovered);
+
+    let first_line = contents.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+    let results: Vec<_> = if first_line.trim_start().starts_with('[') || first_line.contains('@') {
+        let placements = if first_line.contains('@') {
+            placement::from_text(&contents)
+        } else {
+            placement::from_json(&contents)
+        };
+
+        match placements {
+            Ok(placements) => placements.iter().map(|p| verifier.verify(p)).collect(),
+            Err(e) => {
+                eprintln!("polyrun: can't read {}: {}", file, e);
+                return ExitCode::from(BAD_INPUT);
+            }
+        }
+    } else {
+        let mut grids = Vec::new();
+        let mut grid = String::new();
+        for line in contents.lines().chain([""]) {
+            if !line.trim().is_empty() {
+                grid.push_str(line);
+                grid.push('\n');
+            } else if !grid.is_empty() {
+                grids.push(mem::take(&mut grid));
+            }
+        }
+
+        grids.iter().map(|grid| verifier.verify_letters(grid)).collect()
+    };
+
+    if results.is_empty() {
+        eprintln!("polyrun: there are no solutions in {}", file);
+        return ExitCode::from(BAD_INPUT);
+    }
+
+    for (n, result) in results.iter().enumerate() {
+        match result {
+            Ok(_) => println!("Solution {}: correct", n + 1),
+            Err(violations) => {
+                println!("Solution {}:", n + 1);
+                violations.iter().for_each(|v| println!("  {}", v));
+            }
+        }
+    }
+
+    if results.iter().all(|r| r.is_ok()) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(WRONG_SOLUTION)
+    }
+}
+
+// One character per cell: the name of each piece, and '.' for cells that
+// aren't covered. The layers of a solid are separated by a line of dashes.
+fn letters<P: Polyomino>(b: &Board<P>) -> String {
+    let mut res = String::new();
+
+    for z in 0..b.get_depth() {
+        if z > 0 {
+            res.push_str("-\n");
+        }
+
+        for y in b.row_range() {
+            let row: String = b.col_range()
+                .map(|x| match b.get_3d(x, y, z) {
+                    BoardState::Void => ' ',
+                    BoardState::Empty | BoardState::Reserved => '.',
+                    BoardState::Fixed(label) => label,
+                    BoardState::Full(p, ..) => p.name().unwrap_or('#'),
+                })
+                .collect();
+
+            res.push_str(row.trim_end());
+            res.push('\n');
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use polyomino::point::Lattice;
+    use polyomino::utils::Orientations;
+
+    use crate::parse_args;
+    use crate::Command;
+    use crate::Format;
+    use crate::Mode;
+
+    fn args(s: &str) -> impl Iterator<Item = String> + '_ {
+        s.split_whitespace().map(String::from)
+    }
+
+    #[test]
+    fn defaults() {
+        let options = parse_args(args("10x6")).unwrap().unwrap();
+
+        assert_eq!(options.command, Command::Solve);
+        assert_eq!(options.board, "10x6");
+        assert_eq!(options.pieces, "pentominoes");
+        assert_eq!(options.mode, Mode::Count);
+        assert_eq!(options.format, Format::Boxes);
+        assert_eq!(options.threads, 1);
+    }
+
+    #[test]
+    fn options() {
+        let options = parse_args(args("-m all --pieces hexominoes data/knight.board -f letters -t 4 -u 2"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(options.board, "data/knight.board");
+        assert_eq!(options.pieces, "hexominoes");
+        assert_eq!(options.mode, Mode::All);
+        assert_eq!(options.format, Format::Letters);
+        assert_eq!(options.threads, 4);
+        assert_eq!(options.uncovered, 2);
+        assert_eq!(options.seed, None);
+        assert_eq!(parse_args(args("10x6 --seed 42")).unwrap().unwrap().seed, Some(42));
+        assert!(!options.distinct);
+        assert!(parse_args(args("-d 10x6")).unwrap().unwrap().distinct);
+        assert!(parse_args(args("-s rectangular 10x6")).unwrap().unwrap().distinct);
+        assert!(!parse_args(args("-s none 10x6")).unwrap().unwrap().distinct);
+
+        let options = parse_args(args("calendar data/calendar.board -p data/calendar.poly -y 2026 -l Oct,18"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(options.command, Command::Calendar);
+        assert_eq!(options.board, "data/calendar.board");
+        assert_eq!(options.year, Some(2026));
+        assert_eq!(options.leave, vec!["Oct", "18"]);
+
+        let options = parse_args(args("verify 10x6 solutions.txt -u 1")).unwrap().unwrap();
+        assert_eq!(options.command, Command::Verify);
+        assert_eq!(options.board, "10x6");
+        assert_eq!(options.solutions, "solutions.txt");
+
+        let options = parse_args(args("estimate 10x6 --probes 500")).unwrap().unwrap();
+        assert_eq!(options.command, Command::Estimate);
+        assert_eq!(options.probes, 500);
+        assert_eq!(parse_args(args("10x6 -f svg")).unwrap().unwrap().format, Format::Svg);
+        assert_eq!(parse_args(args("10x6 -f ascii")).unwrap().unwrap().format, Format::Ascii);
+        assert_eq!(parse_args(args("10x6 -f placements")).unwrap().unwrap().format, Format::Placements);
+        assert_eq!(options.lattice, None);
+        assert_eq!(parse_args(args("10x6 --lattice triangle")).unwrap().unwrap().lattice, Some(Lattice::Triangular));
+        assert_eq!(parse_args(args("10x6 --lattice hexagon")).unwrap().unwrap().lattice, Some(Lattice::Hexagonal));
+        assert_eq!(parse_args(args("3x3x3 --lattice cube")).unwrap().unwrap().lattice, Some(Lattice::Cubic));
+        assert_eq!(options.orientations, None);
+        assert_eq!(parse_args(args("3x3x3 --orientations all")).unwrap().unwrap().orientations, Some(Orientations::All));
+        assert_eq!(parse_args(args("10x6 --orientations rotations")).unwrap().unwrap().orientations, Some(Orientations::Rotations));
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert!(parse_args(args("10x6 --help")).unwrap().is_none());
+        assert!(parse_args(args("")).is_err());
+        assert!(parse_args(args("10x6 8x8")).is_err());
+        assert!(parse_args(args("10x6 -m")).is_err());
+        assert!(parse_args(args("10x6 -m some")).is_err());
+        assert!(parse_args(args("10x6 -t 0")).is_err());
+        assert!(parse_args(args("10x6 -u -1")).is_err());
+        assert!(parse_args(args("10x6 --seed x")).is_err());
+        assert!(parse_args(args("estimate 10x6 --probes 0")).is_err());
+        assert!(parse_args(args("calendar 10x6 -y next")).is_err());
+        assert!(parse_args(args("10x6 calendar")).is_err());
+        assert!(parse_args(args("verify 10x6")).is_err());
+        assert!(parse_args(args("10x6 solutions.txt")).is_err());
+        assert!(parse_args(args("10x6 --colour red")).is_err());
+        assert!(parse_args(args("10x6 --lattice octagon")).is_err());
+        assert!(parse_args(args("10x6 --orientations some")).is_err());
+    }
 }
@@ -0,0 +1,275 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::board::Board;
+use crate::board::BoardState;
+use crate::point::Point;
+use crate::point::SimplePoint;
+use crate::polyomino::Polyomino;
+
+/// Variation `variation` of candidate `candidate`, placed with its origin at
+/// `anchor`. In JSON it is an object such as
+/// `{"piece": 3, "orientation": 5, "x": 0, "y": 2}`, with a `"z"` for the
+/// layer of a solid board when it isn't the first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Placement {
+    #[serde(rename = "piece")]
+    pub candidate: usize,
+    #[serde(rename = "orientation")]
+    pub variation: usize,
+    #[serde(flatten)]
+    pub anchor: SimplePoint,
+}
+
+impl Placement {
+    // The candidate of a placement that leaves the cell at its anchor
+    // uncovered
+    pub(crate) const GAP: usize = usize::MAX;
+
+    pub(crate) fn gap(cell: SimplePoint) -> Placement {
+        Placement { candidate: Placement::GAP, variation: 0, anchor: cell }
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}@{}", self.candidate, self.variation, position(&self.anchor))
+    }
+}
+
+// A cell as x,y, or x,y,z if it isn't in the first layer
+fn position(pt: &SimplePoint) -> String {
+    match pt.z {
+        0 => format!("{},{}", pt.x, pt.y),
+        z => format!("{},{},{}", pt.x, pt.y, z),
+    }
+}
+
+/// Why placements can't be read or put on a board.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PlacementError {
+    /// The text is not in the expected format.
+    Syntax { line: usize, message: String },
+    /// There is no candidate with this index.
+    UnknownPiece(usize),
+    /// The candidate has no variation with this index.
+    UnknownOrientation { piece: usize, orientation: usize },
+    /// The piece goes off the board, or onto a cell that isn't empty.
+    DoesNotFit(Placement),
+    /// The piece on the board with its origin at this cell is not a
+    /// variation in the candidates.
+    NotACandidate(SimplePoint),
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlacementError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            PlacementError::UnknownPiece(piece) => write!(f, "there is no piece {}", piece),
+            PlacementError::UnknownOrientation { piece, orientation } =>
+                write!(f, "piece {} has no orientation {}", piece, orientation),
+            PlacementError::DoesNotFit(placement) => write!(f, "{} doesn't fit on the board", placement),
+            PlacementError::NotACandidate(origin) =>
+                write!(f, "the piece at {} is not one of the pieces", position(origin)),
+        }
+    }
+}
+
+impl Error for PlacementError {}
+
+/// The placements of the pieces on a board, going down each column from the
+/// cell at each piece's origin. The pieces must be variations in
+/// `candidates`, as they are on the boards a `Solver` returns, and any other
+/// piece is a `PlacementError::NotACandidate`. Pre-placed pieces and
+/// uncovered cells are part of the board, not placements.
+pub fn placements<P: Polyomino>(board: &Board<P>, candidates: &[Vec<P>]) -> Result<Vec<Placement>, PlacementError> {
+    let mut res = Vec::new();
+
+    for pt in board.points() {
+        if let BoardState::Full(p, 0, ax, ay, az) = board.get_3d(pt.x, pt.y, pt.z) {
+            let anchor = SimplePoint::new_3d(ax, ay, az);
+            let (candidate, variation) = candidates.iter()
+                .enumerate()
+                .find_map(|(i, vs)| vs.iter().position(|v| std::ptr::eq(v, p)).map(|v| (i, v)))
+                .ok_or(PlacementError::NotACandidate(anchor))?;
+
+            res.push(Placement { candidate, variation, anchor });
+        }
+    }
+
+    Ok(res)
+}
+
+/// A copy of `board` with the pieces added where the placements say.
+pub fn to_board<'a, P: Polyomino>(board: &Board<'a, P>, candidates: &'a [Vec<P>],
+                                  placements: &[Placement]) -> Result<Board<'a, P>, PlacementError> {
+    let mut res = board.clone();
+
+    for placement in placements {
+        let variations = candidates.get(placement.candidate)
+            .ok_or(PlacementError::UnknownPiece(placement.candidate))?;
+        let p = variations.get(placement.variation)
+            .ok_or(PlacementError::UnknownOrientation { piece: placement.candidate, orientation: placement.variation })?;
+
+        if !res.add_polyomino(p, &placement.anchor) {
+            return Err(PlacementError::DoesNotFit(*placement));
+        }
+    }
+
+    Ok(res)
+}
+
+/// The solutions as a JSON array with an array of placements for each.
+pub fn to_json(solutions: &[Vec<Placement>]) -> String {
+    serde_json::to_string(solutions).unwrap()
+}
+
+/// Reads solutions written by `to_json`.
+pub fn from_json(contents: &str) -> Result<Vec<Vec<Placement>>, PlacementError> {
+    serde_json::from_str(contents).map_err(|e| PlacementError::Syntax { line: e.line(), message: e.to_string() })
+}
+
+/// The solutions in a compact text format, with one line for each. Each
+/// placement is written as `piece:orientation@x,y`, or
+/// `piece:orientation@x,y,z` in a layer after the first of a solid board,
+/// separated by spaces.
+pub fn to_text(solutions: &[Vec<Placement>]) -> String {
+    solutions.iter()
+        .map(|placements| placements.iter().map(|p| p.to_string()).collect::<Vec<String>>().join(" ") + "\n")
+        .collect()
+}
+
+/// Reads solutions written by `to_text`. Blank lines are skipped.
+pub fn from_text(contents: &str) -> Result<Vec<Vec<Placement>>, PlacementError> {
+    let mut res = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let placements = line.split_whitespace()
+            .map(|word| read_placement(word).ok_or_else(|| PlacementError::Syntax {
+                line: i + 1,
+                message: format!("'{}' is not a placement such as 3:5@0,2", word),
+            }))
+            .collect::<Result<Vec<Placement>, PlacementError>>()?;
+        res.push(placements);
+    }
+
+    Ok(res)
+}
+
+fn read_placement(word: &str) -> Option<Placement> {
+    let (piece, rest) = word.split_once(':')?;
+    let (orientation, anchor) = rest.split_once('@')?;
+    let numbers = anchor.split(',').map(|n| n.parse().ok()).collect::<Option<Vec<i16>>>()?;
+    let anchor = match numbers[..] {
+        [x, y] => SimplePoint::new(x, y),
+        [x, y, z] => SimplePoint::new_3d(x, y, z),
+        _ => return None,
+    };
+
+    Some(Placement {
+        candidate: piece.parse().ok()?,
+        variation: orientation.parse().ok()?,
+        anchor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::placement;
+    use crate::placement::Placement;
+    use crate::placement::PlacementError;
+    use crate::point::CubePoint;
+    use crate::point::Point;
+    use crate::point::SimplePoint;
+    use crate::polyomino::SimplePolyomino;
+    use crate::solver::Solver;
+    use crate::utils;
+    use crate::utils::PredefinedPolyominoes;
+    use crate::utils::Restrictions;
+
+    type Poly = SimplePolyomino<SimplePoint>;
+
+    #[test]
+    fn round_trip() {
+        let pentominoes: Vec<Poly> = utils::get_polyominoes(PredefinedPolyominoes::Pentominoes).unwrap();
+        let polys = utils::build_variations(&pentominoes, Restrictions::None);
+        let board = Board::new(10, 6);
+        let mut b = board.clone();
+        let mut solver = Solver::new(&mut b, &polys);
+
+        let solutions: Vec<Board<Poly>> = solver.solutions().take(5).collect();
+        let exported: Vec<Vec<Placement>> = solutions.iter().map(|s| placement::placements(s, &polys).unwrap()).collect();
+        assert!(exported.iter().all(|placements| placements.len() == 12));
+
+        assert_eq!(placement::from_json(&placement::to_json(&exported)), Ok(exported.clone()));
+        assert_eq!(placement::from_text(&placement::to_text(&exported)), Ok(exported.clone()));
+
+        for (s, placements) in solutions.iter().zip(&exported) {
+            let rebuilt = placement::to_board(&board, &polys, placements).unwrap();
+            assert_eq!(rebuilt.to_string(), s.to_string());
+        }
+    }
+
+    #[test]
+    fn solid() {
+        let soma: Vec<SimplePolyomino<CubePoint>> = utils::read_polyominoes_from_file("data/soma.poly").unwrap();
+        let polys = utils::build_variations(&soma, Restrictions::None);
+        let board = Board::new_box(3, 3, 3);
+        let mut b = board.clone();
+        let s = Solver::new(&mut b, &polys).solutions().next().unwrap();
+
+        // Pieces that start in a higher layer are placed there
+        let placements = placement::placements(&s, &polys).unwrap();
+        assert!(placements.iter().any(|p| p.anchor.z > 0));
+        assert_eq!(placement::from_text(&placement::to_text(std::slice::from_ref(&placements))), Ok(vec![placements.clone()]));
+        assert_eq!(placement::to_board(&board, &polys, &placements).unwrap().to_string(), s.to_string());
+    }
+
+    #[test]
+    fn formats() {
+        let placements = vec![vec![Placement { candidate: 3, variation: 5, anchor: SimplePoint::new(0, 2) }]];
+
+        assert_eq!(placement::to_json(&placements), r#"[[{"piece":3,"orientation":5,"x":0,"y":2}]]"#);
+        assert_eq!(placement::to_text(&placements), "3:5@0,2\n");
+        assert_eq!(placement::from_json("[[ {\"y\": 2, \"x\": 0, \"orientation\": 5, \"piece\": 3} ]]"), Ok(placements.clone()));
+        assert_eq!(placement::from_text("\n3:5@0,2\n\n"), Ok(placements));
+
+        // Pieces in a layer after the first of a solid board give their layer
+        let solid = vec![vec![Placement { candidate: 1, variation: 0, anchor: SimplePoint::new_3d(2, 0, 1) }]];
+        assert_eq!(placement::to_json(&solid), r#"[[{"piece":1,"orientation":0,"x":2,"y":0,"z":1}]]"#);
+        assert_eq!(placement::to_text(&solid), "1:0@2,0,1\n");
+        assert_eq!(placement::from_text("1:0@2,0,1\n"), Ok(solid.clone()));
+        assert_eq!(placement::from_json(&placement::to_json(&solid)), Ok(solid));
+
+        assert_eq!(placement::from_text("0:0@0,0\n3:5@0\n"),
+                   Err(PlacementError::Syntax { line: 2, message: "'3:5@0' is not a placement such as 3:5@0,2".to_string() }));
+        assert!(matches!(placement::from_json("[[{\"piece\": 3}]]"), Err(PlacementError::Syntax { line: 1, .. })));
+    }
+
+    #[test]
+    fn bad_placements() {
+        let pentominoes: Vec<Poly> = utils::get_polyominoes(PredefinedPolyominoes::Pentominoes).unwrap();
+        let polys = utils::build_variations(&pentominoes, Restrictions::None);
+        let board = Board::new(5, 5);
+        let at = |candidate, variation, x, y| Placement { candidate, variation, anchor: SimplePoint::new(x, y) };
+
+        assert_eq!(placement::to_board(&board, &polys, &[at(12, 0, 0, 0)]).err(), Some(PlacementError::UnknownPiece(12)));
+        assert_eq!(placement::to_board(&board, &polys, &[at(0, 8, 0, 0)]).err(),
+                   Some(PlacementError::UnknownOrientation { piece: 0, orientation: 8 }));
+        assert_eq!(placement::to_board(&board, &polys, &[at(1, 0, 0, 0), at(1, 0, 0, 0)]).err(),
+                   Some(PlacementError::DoesNotFit(at(1, 0, 0, 0))));
+
+        // A board with a piece that isn't one of the candidates
+        let mut b = board.clone();
+        assert!(b.add_polyomino(&pentominoes[0], &SimplePoint::new(0, 0)));
+        assert_eq!(placement::placements(&b, &polys), Err(PlacementError::NotACandidate(SimplePoint::new(0, 0))));
+    }
+}
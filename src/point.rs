@@ -3,26 +3,110 @@ use std::fmt;
 use std::hash::Hash;
 
 use point_derive::OrdForPoint;
+use serde::Deserialize;
+use serde::Serialize;
+
+// The grid that the cells of pieces and boards are on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lattice {
+    Square,
+    Triangular,
+    Hexagonal,
+    Cubic,
+}
+
+impl Lattice {
+    // The number of rotations that map the lattice onto itself
+    pub fn rotations(&self) -> usize {
+        match self {
+            Lattice::Square => 4,
+            Lattice::Triangular | Lattice::Hexagonal => 6,
+            Lattice::Cubic => 24,
+        }
+    }
+}
+
+// A cell of a lattice. The defaults are for the square grid.
+pub trait Point : Ord + PartialOrd + Copy + Hash + fmt::Display + Sync {
+    const LATTICE: Lattice = Lattice::Square;
 
-pub trait Point : Ord + PartialOrd + Copy + Hash + fmt::Display {
     fn new(x: i16, y: i16) -> Self;
+
+    // The cell in column x and row y of layer z. Flat lattices only have
+    // layer 0.
+    fn new_3d(x: i16, y: i16, z: i16) -> Self {
+        debug_assert_eq!(z, 0);
+        Self::new(x, y)
+    }
     
     fn x(&self) -> i16;
     fn set_x(&mut self, x: i16);
     
     fn y(&self) -> i16;
     fn set_y(&mut self, x: i16);
+
+    // The layer the cell is in, counting up from 0
+    fn z(&self) -> i16 {
+        0
+    }
+
+    // The cell turned about the origin by the smallest rotation of the
+    // lattice, a quarter turn for squares
+    fn rotate(&self) -> Self {
+        Self::new(-self.y(), self.x())
+    }
+
+    // The cell reflected in the y axis
+    fn flip(&self) -> Self {
+        Self::new(-self.x(), self.y())
+    }
+
+    // The cell turned by rotation i of the lattice, where i is less than
+    // LATTICE.rotations() and rotation 0 leaves it where it is. On a flat
+    // lattice this is i turns by rotate.
+    fn turn(&self, i: usize) -> Self {
+        (0..i).fold(*self, |p, _| p.rotate())
+    }
+
+    // True if moving a cell by dx and dy gives a cell of the same shape
+    fn is_translation(_dx: i16, _dy: i16) -> bool {
+        true
+    }
+
+    // The translation that moves cells whose smallest coordinates are min_x
+    // and min_y as close to the origin as the lattice allows
+    fn origin_offset(min_x: i16, min_y: i16) -> (i16, i16) {
+        (min_x, min_y)
+    }
+
+    // Where the cells that share an edge or a face with the cell at x, y
+    // are, relative to it, as (dx, dy, dz)
+    fn neighbours(_x: i16, _y: i16) -> &'static [(i16, i16, i16)] {
+        &[(0, -1, 0), (-1, 0, 0), (0, 1, 0), (1, 0, 0)]
+    }
 }
 
-#[derive(Debug, Clone, Copy, OrdForPoint)]
+// A cell of the square grid. It is also the position of a cell on a board of
+// any lattice, where z is the layer of a solid board and 0 on a flat one.
+#[derive(Debug, Clone, Copy, OrdForPoint, Serialize, Deserialize)]
 pub struct SimplePoint {
     pub x: i16,
     pub y: i16,
+    #[serde(default, skip_serializing_if = "is_first_layer")]
+    pub z: i16,
+}
+
+fn is_first_layer(z: &i16) -> bool {
+    *z == 0
 }
 
 impl Point for SimplePoint {
     fn new(new_x: i16, new_y: i16) -> SimplePoint {
-        SimplePoint { x: new_x, y: new_y }
+        SimplePoint { x: new_x, y: new_y, z: 0 }
+    }
+
+    fn new_3d(new_x: i16, new_y: i16, new_z: i16) -> SimplePoint {
+        SimplePoint { x: new_x, y: new_y, z: new_z }
     }
     
     fn x(&self) -> i16 {
@@ -40,6 +124,10 @@ impl Point for SimplePoint {
     fn set_y(&mut self, new_y: i16) {
         self.y = new_y;
     }
+
+    fn z(&self) -> i16 {
+        self.z
+    }
 }
 
 impl fmt::Display for SimplePoint {
@@ -50,10 +138,227 @@ impl fmt::Display for SimplePoint {
     }
 }
 
+// A cell of a grid of equilateral triangles, in rows with y growing
+// downwards as on a board. Along a row the cells point up and down in turn,
+// with the cell at the origin pointing up, so a cell points up if x + y is
+// even. A cell shares an edge with the cells on either side of it, and with
+// the cell below it if it points up or the one above it if it points down.
+#[derive(Debug, Clone, Copy, OrdForPoint, Serialize, Deserialize)]
+pub struct TrianglePoint {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl TrianglePoint {
+    pub fn points_up(&self) -> bool {
+        (self.x + self.y).rem_euclid(2) == 0
+    }
+}
+
+impl Point for TrianglePoint {
+    const LATTICE: Lattice = Lattice::Triangular;
+
+    fn new(new_x: i16, new_y: i16) -> TrianglePoint {
+        TrianglePoint { x: new_x, y: new_y }
+    }
+
+    fn x(&self) -> i16 {
+        self.x
+    }
+
+    fn set_x(&mut self, new_x: i16) {
+        self.x = new_x;
+    }
+
+    fn y(&self) -> i16 {
+        self.y
+    }
+
+    fn set_y(&mut self, new_y: i16) {
+        self.y = new_y;
+    }
+
+    // A sixth of a turn about the corner at the top of the cell at the
+    // origin. Cells that point up are turned into cells that point down and
+    // the other way round.
+    fn rotate(&self) -> TrianglePoint {
+        let up = self.points_up() as i16;
+
+        TrianglePoint::new((self.x - 3 * self.y).div_euclid(2) - up, (self.x + self.y).div_euclid(2))
+    }
+
+    // A move that changes x + y by an odd number would turn the cells upside
+    // down
+    fn is_translation(dx: i16, dy: i16) -> bool {
+        (dx + dy).rem_euclid(2) == 0
+    }
+
+    fn origin_offset(min_x: i16, min_y: i16) -> (i16, i16) {
+        if TrianglePoint::is_translation(min_x, min_y) {
+            (min_x, min_y)
+        } else {
+            (min_x - 1, min_y)
+        }
+    }
+
+    fn neighbours(x: i16, y: i16) -> &'static [(i16, i16, i16)] {
+        if TrianglePoint::new(x, y).points_up() {
+            &[(-1, 0, 0), (1, 0, 0), (0, 1, 0)]
+        } else {
+            &[(0, -1, 0), (-1, 0, 0), (1, 0, 0)]
+        }
+    }
+}
+
+impl fmt::Display for TrianglePoint {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "X")
+    }
+}
+
+// A cell of a grid of hexagons, in axial coordinates: x counts along a row
+// and y counts rows downwards, each row half a cell further right than the
+// one above it. Besides the cells on either side of it, a cell shares an edge
+// with the cell at the same x and the one after it in the row above, and
+// with the cell at the same x and the one before it in the row below.
+#[derive(Debug, Clone, Copy, OrdForPoint, Serialize, Deserialize)]
+pub struct HexPoint {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl Point for HexPoint {
+    const LATTICE: Lattice = Lattice::Hexagonal;
+
+    fn new(new_x: i16, new_y: i16) -> HexPoint {
+        HexPoint { x: new_x, y: new_y }
+    }
+
+    fn x(&self) -> i16 {
+        self.x
+    }
+
+    fn set_x(&mut self, new_x: i16) {
+        self.x = new_x;
+    }
+
+    fn y(&self) -> i16 {
+        self.y
+    }
+
+    fn set_y(&mut self, new_y: i16) {
+        self.y = new_y;
+    }
+
+    // A sixth of a turn about the centre of the cell at the origin
+    fn rotate(&self) -> HexPoint {
+        HexPoint::new(-self.y, self.x + self.y)
+    }
+
+    // The cell reflected in the vertical line through the centre of the
+    // cell at the origin
+    fn flip(&self) -> HexPoint {
+        HexPoint::new(-self.x - self.y, self.y)
+    }
+
+    fn neighbours(_x: i16, _y: i16) -> &'static [(i16, i16, i16)] {
+        &[(0, -1, 0), (1, -1, 0), (-1, 0, 0), (1, 0, 0), (-1, 1, 0), (0, 1, 0)]
+    }
+}
+
+impl fmt::Display for HexPoint {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "X")
+    }
+}
+
+// A cell of a grid of cubes, in column x and row y of layer z
+#[derive(Debug, Clone, Copy, OrdForPoint, Serialize, Deserialize)]
+pub struct CubePoint {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+impl Point for CubePoint {
+    const LATTICE: Lattice = Lattice::Cubic;
+
+    fn new(new_x: i16, new_y: i16) -> CubePoint {
+        CubePoint { x: new_x, y: new_y, z: 0 }
+    }
+
+    fn new_3d(new_x: i16, new_y: i16, new_z: i16) -> CubePoint {
+        CubePoint { x: new_x, y: new_y, z: new_z }
+    }
+
+    fn x(&self) -> i16 {
+        self.x
+    }
+
+    fn set_x(&mut self, new_x: i16) {
+        self.x = new_x;
+    }
+
+    fn y(&self) -> i16 {
+        self.y
+    }
+
+    fn set_y(&mut self, new_y: i16) {
+        self.y = new_y;
+    }
+
+    fn z(&self) -> i16 {
+        self.z
+    }
+
+    // A quarter turn about the z axis
+    fn rotate(&self) -> CubePoint {
+        CubePoint::new_3d(-self.y, self.x, self.z)
+    }
+
+    // The cell reflected in the plane x = 0. No rotation gives a cube its
+    // mirror image.
+    fn flip(&self) -> CubePoint {
+        CubePoint::new_3d(-self.x, self.y, self.z)
+    }
+
+    // Up to four turns about the z axis, after bringing one of the six faces
+    // of the cube to the top: rotations 0 to 15 tip the cube over about the x
+    // axis and the rest tip it over to either side about the y axis
+    fn turn(&self, i: usize) -> CubePoint {
+        let mut p = (0..i % 4).fold(*self, |p, _| p.rotate());
+
+        match i / 4 {
+            face @ 0..=3 => {
+                for _ in 0..face {
+                    p = CubePoint::new_3d(p.x, -p.z, p.y);
+                }
+            }
+            4 => p = CubePoint::new_3d(p.z, p.y, -p.x),
+            _ => p = CubePoint::new_3d(-p.z, p.y, p.x),
+        }
+
+        p
+    }
+
+    fn neighbours(_x: i16, _y: i16) -> &'static [(i16, i16, i16)] {
+        &[(0, 0, -1), (0, -1, 0), (-1, 0, 0), (0, 1, 0), (1, 0, 0), (0, 0, 1)]
+    }
+}
+
+impl fmt::Display for CubePoint {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "X")
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::point::CubePoint;
+    use crate::point::HexPoint;
     use crate::point::Point;
     use crate::point::SimplePoint;
+    use crate::point::TrianglePoint;
 
     #[test]
     fn ord() {
@@ -68,11 +373,84 @@ mod tests {
         assert!(p07 < p16);
         assert!(p07 == p07);
     }
-}
 
+    #[test]
+    fn triangles() {
+        let cells: Vec<TrianglePoint> = (-3..4).flat_map(|x| (-3..4).map(move |y| TrianglePoint::new(x, y))).collect();
 
+        for &p in &cells {
+            let mut turned = p;
+            for _ in 0..6 {
+                assert_ne!(turned.rotate().points_up(), turned.points_up());
+                turned = turned.rotate();
+            }
+            assert_eq!(turned, p);
+            assert_eq!(p.flip().points_up(), p.points_up());
 
+            // Turning keeps neighbours next to each other
+            for &(dx, dy, _) in TrianglePoint::neighbours(p.x, p.y) {
+                let (a, b) = (p.rotate(), TrianglePoint::new(p.x + dx, p.y + dy).rotate());
+                assert!(TrianglePoint::neighbours(a.x, a.y).contains(&(b.x - a.x, b.y - a.y, 0)));
+            }
+        }
 
+        assert!(TrianglePoint::new(0, 0).points_up());
+        assert!(!TrianglePoint::new(-1, 0).points_up());
+        assert!(!TrianglePoint::is_translation(1, 0));
+        assert_eq!(TrianglePoint::origin_offset(1, 0), (0, 0));
+        assert_eq!(TrianglePoint::origin_offset(-2, 1), (-3, 1));
+    }
 
+    #[test]
+    fn hexagons() {
+        let origin = HexPoint::new(0, 0);
+        let around: Vec<HexPoint> = HexPoint::neighbours(0, 0).iter().map(|&(x, y, _)| HexPoint::new(x, y)).collect();
+
+        for &p in &around {
+            let mut turned = p;
+            for _ in 0..6 {
+                turned = turned.rotate();
+                assert!(around.contains(&turned));
+                assert!(around.contains(&turned.flip()));
+            }
+            assert_eq!(turned, p);
+            assert_eq!(p.flip().flip(), p);
+        }
 
+        assert_eq!(origin.rotate(), origin);
+        assert_eq!(HexPoint::new(1, 0).rotate(), HexPoint::new(0, 1));
+        assert_eq!(HexPoint::new(1, 0).flip(), HexPoint::new(-1, 0));
+        assert_eq!(HexPoint::new(0, 1).flip(), HexPoint::new(-1, 1));
+    }
+
+    #[test]
+    fn cubes() {
+        let p = CubePoint::new_3d(1, 2, 3);
+        assert_eq!((p.x(), p.y(), p.z()), (1, 2, 3));
+        assert_eq!(CubePoint::new(-1, 0), CubePoint::new_3d(-1, 0, 0));
+        assert_eq!(CubePoint::new_3d(40, 0, 0).x(), 40);
 
+        // The 24 rotations are all different, and mirror images are none of them
+        let turned: Vec<CubePoint> = (0..24).map(|i| p.turn(i)).collect();
+        for (i, a) in turned.iter().enumerate() {
+            assert!(turned[i + 1..].iter().all(|b| b != a));
+            assert!(!turned.contains(&a.flip()));
+            assert_eq!(a.x * a.x + a.y * a.y + a.z * a.z, 14);
+        }
+        assert_eq!(p.turn(0), p);
+        assert_eq!(p.turn(1), p.rotate());
+        assert_eq!(p.turn(4), CubePoint::new_3d(1, -3, 2));
+
+        // Points are ordered a layer at a time, as a board keeps its cells
+        assert!(CubePoint::new_3d(0, 1, 0) < CubePoint::new_3d(1, 0, 0));
+        assert!(CubePoint::new_3d(5, 5, 0) < CubePoint::new_3d(0, 0, 1));
+        assert!(CubePoint::new_3d(-5, 0, 1) < CubePoint::new_3d(0, 0, 1));
+
+        // Each neighbour is a face away, and turning keeps them next to each
+        // other
+        let around: Vec<CubePoint> = CubePoint::neighbours(0, 0).iter().map(|&(x, y, z)| CubePoint::new_3d(x, y, z)).collect();
+        for i in 0..24 {
+            assert!(around.iter().all(|q| around.contains(&q.turn(i)) && around.contains(&q.flip())));
+        }
+    }
+}
@@ -1,15 +1,15 @@
 use std::cmp;
 use std::fmt;
 use std::hash::Hash;
+use std::hash::Hasher;
 use std::marker::Sized;
 use std::slice::Iter;
 
-use rustc_hash::FxHashSet;
-
+use crate::point::Lattice;
 use crate::point::Point;
 use crate::point::SimplePoint;
 
-pub trait Polyomino : Sized + fmt::Display + Hash + Clone + PartialEq + Eq {
+pub trait Polyomino : Sized + fmt::Display + Hash + Clone + PartialEq + Eq + Sync {
     type Pt : Point;
 
     fn new(ptrs: Vec<Self::Pt>) -> Self;
@@ -19,79 +19,157 @@ pub trait Polyomino : Sized + fmt::Display + Hash + Clone + PartialEq + Eq {
     fn set_points(&mut self, pts: Vec<Self::Pt>);
 
     fn get_nth(&self, n: usize) -> Option<&Self::Pt>;
+
+    // The letter the piece is shown with, if it has one. The name plays no
+    // part in comparing pieces, and is kept by rotations and reflections.
+    fn name(&self) -> Option<char>;
+
+    fn set_name(&mut self, name: Option<char>);
     
-    // The largest x and y in the set of points. Note: this point may not be in the polyomino
+    // The largest x, y and z in the set of points. Note: this point may not be in the polyomino
     fn bbox_top_right(&self) -> SimplePoint {
         self.iter().fold(SimplePoint::new(0, 0), |a, p| SimplePoint {
             x: cmp::max(a.x(), p.x()),
             y: cmp::max(a.y(), p.y()),
+            z: cmp::max(a.z(), p.z()),
         })
     }
     
+    // The smallest rotation of the lattice, moved back to the origin
     fn rotate(&self) -> Self {
         let mut rot = self.clone();
-        let height = rot.bbox_top_right().y();
-        
-        rot.set_points(
-            self.iter()
-                .map(|p| { let mut new_p:Self::Pt = *p; new_p.set_x(height - p.y()); new_p.set_y(p.x()); new_p })
-                .collect());
+        rot.set_points(self.iter().map(|p| p.rotate()).collect());
 
-        rot
+        rot.normalize()
     }
     
     fn flip(&self) -> Self {
         let mut flipped = self.clone();
-        let width = flipped.bbox_top_right().x();
+        flipped.set_points(self.iter().map(|p| p.flip()).collect());
 
-        flipped.set_points(
-            self.iter()
-                .map(|p| { let mut new_p:Self::Pt = *p; new_p.set_x(width - p.x()); new_p.set_y(p.y()); new_p })
-                .collect(),
-            );
-
-        flipped
+        flipped.normalize()
     }
             
+    // The polyomino turned by rotation i of the lattice (see Point::turn),
+    // moved back to the origin
+    fn turn(&self, i: usize) -> Self {
+        let mut turned = self.clone();
+        turned.set_points(self.iter().map(|p| p.turn(i)).collect());
+
+        turned.normalize()
+    }
+
+    // The different rotations of the piece, in the order of the rotations of
+    // the lattice, starting with the piece itself. The order is part of the
+    // exported placements, so it mustn't depend on hashing.
     fn make_rotations(&self) -> Vec<Self> {
-        let mut res = FxHashSet::default();
+        let mut res: Vec<Self> = Vec::new();
 
-        res.insert(self.clone());
-        res.insert(self.clone().rotate());
-        res.insert(self.clone().rotate().rotate());
-        res.insert(self.clone().rotate().rotate().rotate());
+        for i in 0..Self::Pt::LATTICE.rotations() {
+            let turned = if i == 0 { self.clone() } else { self.turn(i) };
+            if !res.contains(&turned) {
+                res.push(turned);
+            }
+        }
 
-        let rotations = res.drain().collect();
-        rotations
+        res
     }
 
-   fn make_all_variations(&self) -> Vec<Self> {
-        let mut res = FxHashSet::default();
+    // The rotations of the piece followed by those of its mirror image that
+    // are different, in the same order as make_rotations
+    fn make_all_variations(&self) -> Vec<Self> {
+        let mut res = self.make_rotations();
 
-        res.insert(self.clone());
-        res.insert(self.clone().rotate());
-        res.insert(self.clone().rotate().rotate());
-        res.insert(self.clone().rotate().rotate().rotate());
+        for flipped in self.clone().flip().make_rotations() {
+            if !res.contains(&flipped) {
+                res.push(flipped);
+            }
+        }
 
-        res.insert(self.clone().flip());
-        res.insert(self.clone().flip().rotate());
-        res.insert(self.clone().flip().rotate().rotate());
-        res.insert(self.clone().flip().rotate().rotate().rotate());
+        res
+    }
+
+    // Every way the piece can be put down. A flat piece can be turned over,
+    // which gives its mirror image, but a solid one can only be turned.
+    fn make_orientations(&self) -> Vec<Self> {
+        if Self::Pt::LATTICE == Lattice::Cubic {
+            self.make_rotations()
+        } else {
+            self.make_all_variations()
+        }
+    }
+
+    // The same polyomino moved so that its smallest x, y and z are 0, or as
+    // near to that as the lattice allows
+    fn normalize(&self) -> Self {
+        let min_x = self.iter().map(|p| p.x()).min().unwrap_or(0);
+        let min_y = self.iter().map(|p| p.y()).min().unwrap_or(0);
+        let dz = self.iter().map(|p| p.z()).min().unwrap_or(0);
+        let (dx, dy) = Self::Pt::origin_offset(min_x, min_y);
+
+        let mut res = self.clone();
+        res.set_points(self.iter().map(|p| Self::Pt::new_3d(p.x() - dx, p.y() - dy, p.z() - dz)).collect());
+        res
+    }
+
+    // The rotation or reflection whose sorted points come first. Two pieces
+    // are the same free polyomino if and only if they have the same one, so it
+    // can be used as a key in a hash map.
+    fn canonical_free(&self) -> Self {
+        self.normalize().make_all_variations().into_iter().min_by(|a, b| a.iter().cmp(b.iter())).unwrap()
+    }
+
+    // The rotation whose sorted points come first, without reflections
+    fn canonical_one_sided(&self) -> Self {
+        self.normalize().make_rotations().into_iter().min_by(|a, b| a.iter().cmp(b.iter())).unwrap()
+    }
+
+    // True if the pieces are the same up to translation, rotation and reflection
+    fn is_congruent_to(&self, other: &Self) -> bool {
+        self.iter().len() == other.iter().len() && self.canonical_free() == other.canonical_free()
+    }
+
+    // True if the pieces are the same up to translation and rotation
+    fn is_one_sided_congruent_to(&self, other: &Self) -> bool {
+        self.iter().len() == other.iter().len() && self.canonical_one_sided() == other.canonical_one_sided()
+    }
 
-        let rotations = res.drain().collect();
-        rotations
-   }
-    
     fn show(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Solids are drawn a layer at a time, as in a pieces file, with a '.'
+        // for an empty row above the cells of a layer
+        if Self::Pt::LATTICE == Lattice::Cubic {
+            let (min_z, max_z) = self.iter().fold((i16::MAX, i16::MIN), |(lo, hi), p| (lo.min(p.z()), hi.max(p.z())));
+            let (left, right) = self.iter().fold((i16::MAX, i16::MIN), |(lo, hi), p| (lo.min(p.x()), hi.max(p.x())));
+
+            for z in min_z..=max_z {
+                if z > min_z {
+                    writeln!(f, "-")?;
+                }
+                let height = self.iter().filter(|p| p.z() == z).map(|p| p.y()).max().unwrap_or(0);
+                for y in 0..height + 1 {
+                    let row: String = (left..=right)
+                        .map(|x| if self.iter().any(|p| p.z() == z && p.x() == x && p.y() == y) { 'X' } else { ' ' })
+                        .collect();
+                    writeln!(f, "{}", if row.trim_end().is_empty() { "." } else { row.trim_end() })?;
+                }
+            }
+
+            return Ok(());
+        }
+
         // Inefficient, but it hardly matters
         let SimplePoint {
             x: width,
             y: height,
+            ..
         } = self.bbox_top_right();
         for y in 0..height + 1 {
+            // Turning triangles upside down would change the shape
+            let y = if Self::Pt::LATTICE == Lattice::Square { height - y } else { y };
+
             for x in 0..width + 1 {
-                if let Some(pt) = self.iter().find(|p| p.x() == x && p.y() == (height - y)) {
-                    write!(f, "{}", pt.to_string())?;
+                if let Some(pt) = self.iter().find(|p| p.x() == x && p.y() == y) {
+                    write!(f, "{}", pt)?;
                 } else {
                     write!(f, " ")?;
                 }
@@ -103,9 +181,24 @@ pub trait Polyomino : Sized + fmt::Display + Hash + Clone + PartialEq + Eq {
     }
 }
 
-#[derive(Debug, Hash, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct SimplePolyomino<T: Point> {
     points: Vec<T>,
+    name: Option<char>,
+}
+
+impl <T: Point> PartialEq for SimplePolyomino<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.points == other.points
+    }
+}
+
+impl <T: Point> Eq for SimplePolyomino<T> {}
+
+impl <T: Point> Hash for SimplePolyomino<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.points.hash(state);
+    }
 }
 
 impl <T: Point> Polyomino for SimplePolyomino<T> {
@@ -114,7 +207,7 @@ impl <T: Point> Polyomino for SimplePolyomino<T> {
     fn new(mut points : Vec<T>) -> SimplePolyomino<T> {
         points.sort();
         points.dedup();
-        SimplePolyomino{ points: points}
+        SimplePolyomino{ points, name: None }
     }
 
     fn iter(&self) -> Iter<'_, Self::Pt> {
@@ -130,6 +223,14 @@ impl <T: Point> Polyomino for SimplePolyomino<T> {
         points.dedup();
         self.points = points;
     }
+
+    fn name(&self) -> Option<char> {
+        self.name
+    }
+
+    fn set_name(&mut self, name: Option<char>) {
+        self.name = name;
+    }
 }
 
 
@@ -140,16 +241,154 @@ impl <T: Point> fmt::Display for SimplePolyomino<T> {
 }
 
 #[cfg(test)]
+#[allow(clippy::vec_init_then_push, clippy::assertions_on_constants)]
 mod tests {
+    use crate::error::ParseError;
+    use crate::point::CubePoint;
+    use crate::point::Lattice;
     use crate::point::Point;
     use crate::point::SimplePoint;
+    use crate::point::TrianglePoint;
     use crate::polyomino::Polyomino;
     use crate::polyomino::SimplePolyomino;
     use crate::utils;
     use crate::utils::PredefinedPolyominoes;
     
+    #[test]
+    fn test_set_names() {
+        assert_eq!("pentominoes".parse(), Ok(PredefinedPolyominoes::Pentominoes));
+        assert_eq!(PredefinedPolyominoes::Pentominoes, PredefinedPolyominoes::Size5);
+        assert_eq!("Domino".parse(), Ok(PredefinedPolyominoes::Dominoes));
+        assert_eq!("hexominos".parse(), Ok(PredefinedPolyominoes::Hexominoes));
+        assert!("nonominoes".parse::<PredefinedPolyominoes>().is_ok());
+        assert!("undecominoes".parse::<PredefinedPolyominoes>().is_err());
+        assert_eq!("hexiamonds".parse(), Ok(PredefinedPolyominoes::Size6));
+        assert_eq!(PredefinedPolyominoes::lattice("Hexiamonds"), Lattice::Triangular);
+        assert_eq!(PredefinedPolyominoes::lattice("hexominoes"), Lattice::Square);
+        assert_eq!("tetrahexes".parse(), Ok(PredefinedPolyominoes::Size4));
+        assert_eq!(PredefinedPolyominoes::lattice("Tetrahexes"), Lattice::Hexagonal);
+        assert_eq!(PredefinedPolyominoes::lattice("hexahex"), Lattice::Hexagonal);
+    }
+
+    #[test]
+    fn test_read_string() {
+        type Poly = SimplePolyomino<SimplePoint>;
+
+        // Indented pieces and extra blank lines are fine
+        let polys: Vec<Poly> = utils::read_polyomino_string("  XX\n   X\n\n\n\nXXX\n").unwrap();
+        assert_eq!(polys.len(), 2);
+        assert_eq!(polys[0].iter().map(|p| (p.x(), p.y())).collect::<Vec<_>>(), vec![(0, 0), (0, 1), (1, 1)]);
+
+        match utils::read_polyomino_string::<Poly>("XX\nX\n\nX-X\n") {
+            Err(ParseError::InvalidCharacter { line: 4, column: 2, found: '-' }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match utils::read_polyomino_string::<Poly>("\n  \n") {
+            Err(ParseError::Empty) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match utils::read_polyomino_string::<Poly>("XX\n\nX X\nXXX\n\nX\n X\n") {
+            Err(ParseError::DisconnectedPiece { line: 7, column: 2 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match utils::read_polyomino_string::<Poly>("XX\nX\n\nXXX\n\nX\nXX\n") {
+            Err(ParseError::DuplicatePiece { line: 6, column: 1, first_line: 1 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match utils::read_polyomino_string::<Poly>("XX\nX\n\nAAA\nAB\n") {
+            Err(ParseError::MixedNames { line: 5, column: 2 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match utils::read_polyomino_string::<Poly>("aa\na\n\naaaa\n") {
+            Err(ParseError::DuplicateName { line: 4, column: 1, first_line: 1 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_read_triangles() {
+        type Tri = SimplePolyomino<TrianglePoint>;
+
+        // Pieces of triangles are read as they are drawn on a board, with the
+        // first triangle on each line pointing up if the column plus the line
+        // within the piece is even
+        let polys: Vec<Tri> = utils::read_polyomino_string("XXX\n\nX\nXXX\n").unwrap();
+        assert_eq!(polys[0].iter().map(|p| (p.x(), p.y())).collect::<Vec<_>>(), vec![(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(polys[1].iter().map(|p| (p.x(), p.y())).collect::<Vec<_>>(), vec![(0, 0), (0, 1), (1, 1), (2, 1)]);
+        assert_eq!(polys[0].make_all_variations().len(), 6);
+
+        match utils::read_polyomino_string::<Tri>("X\n X\n") {
+            Err(ParseError::DisconnectedPiece { line: 2, column: 2 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match utils::read_polyomino_string::<Tri>("XX\n\n X\nX\n") {
+            Err(ParseError::DisconnectedPiece { line: 4, column: 1 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_read_cubes() {
+        type Cube = SimplePolyomino<CubePoint>;
+
+        // The layers of a solid piece are separated by a line of dashes, and
+        // a piece and its mirror image are different pieces
+        let polys: Vec<Cube> = utils::read_polyomino_string("A\n-\nAA\n A\n\nB\n-\nB\nBB\n").unwrap();
+        let cells = |p: &Cube| p.iter().map(|p| (p.x, p.y, p.z)).collect::<Vec<_>>();
+        assert_eq!(cells(&polys[0]), vec![(0, 0, 0), (0, 0, 1), (1, 0, 1), (1, 1, 1)]);
+        assert_eq!(cells(&polys[1]), vec![(0, 0, 0), (0, 0, 1), (0, 1, 1), (1, 1, 1)]);
+        assert_eq!(polys[0].to_string(), "X\n-\nXX\n X\n");
+        assert_eq!(polys[0].make_orientations().len(), 12);
+        assert_eq!(polys[0].make_all_variations().len(), 24);
+
+        // A '.' keeps the rows of the layers lined up
+        let polys: Vec<Cube> = utils::read_polyomino_string(".\nX\n-\nX\nX\nX\n").unwrap();
+        assert_eq!(cells(&polys[0]), vec![(0, 1, 0), (0, 0, 1), (0, 1, 1), (0, 2, 1)]);
+        assert_eq!(polys[0].to_string(), ".\nX\n-\nX\nX\nX\n");
+
+        match utils::read_polyomino_string::<Cube>("X\n-\nXX\nX\n\nXX\nX\n-\nX\n") {
+            Err(ParseError::DuplicatePiece { line: 6, column: 1, first_line: 1 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+        match utils::read_polyomino_string::<Cube>("X\n-\n X\n") {
+            Err(ParseError::DisconnectedPiece { line: 3, column: 2 }) => (),
+            other => panic!("{:?}", other.err()),
+        }
+
+        // Layers can be as wide as they like
+        let polys: Vec<Cube> = utils::read_polyomino_string(&format!("X\n-\n{}\n", "X".repeat(20))).unwrap();
+        assert_eq!(cells(&polys[0]).last(), Some(&(19, 0, 1)));
+    }
+
+    #[test]
+    fn test_names() {
+        type Poly = SimplePolyomino<SimplePoint>;
+
+        let names = |set| utils::get_polyominoes::<Poly>(set).unwrap().iter().map(|p| p.name().unwrap()).collect::<String>();
+        let mut pentominoes: Vec<char> = names(PredefinedPolyominoes::Pentominoes).chars().collect();
+        pentominoes.sort();
+
+        assert_eq!(pentominoes.into_iter().collect::<String>(), "FILNPTUVWXYZ");
+        assert_eq!(names(PredefinedPolyominoes::Hexominoes).len(), 35);
+
+        // Names from the file come first, so the L tetromino can't have its own
+        let polys: Vec<Poly> = utils::read_polyomino_string("LL\nLL\n\nXXX\nX\n\nX\nXX\n X\n\n#X\n##\n X\n").unwrap();
+        let names: Vec<Option<char>> = polys.iter().map(|p| p.name()).collect();
+        assert_eq!(names, vec![Some('L'), Some('A'), Some('S'), Some('P')]);
+
+        // Names play no part in equality
+        assert_eq!(polys[1].rotate().name(), Some('A'));
+        assert_eq!(polys[1], Poly::new(polys[1].iter().cloned().collect()));
+    }
+
     #[test]
     fn test_read() {
+        match utils::get_polyominoes::<SimplePolyomino<SimplePoint>>(PredefinedPolyominoes::Monominoes) {
+            Ok(polys) => {
+                assert_eq!(polys.len(), 1);
+            }
+            Err(..) => assert!(false),
+        }
         match utils::get_polyominoes::<SimplePolyomino<SimplePoint>>(PredefinedPolyominoes::Dominoes) {
             Ok(polys) => {
                 assert_eq!(polys.len(), 1);
@@ -192,6 +431,12 @@ mod tests {
             }
             Err(..) => assert!(false),
         }
+        match utils::get_polyominoes::<SimplePolyomino<SimplePoint>>(PredefinedPolyominoes::Nonominoes) {
+            Ok(polys) => {
+                assert_eq!(polys.len(), 1285);
+            }
+            Err(..) => assert!(false),
+        }
     }
 
     fn build_f_pentomino() -> SimplePolyomino<SimplePoint> {
@@ -251,6 +496,23 @@ mod tests {
         SimplePolyomino::new(v)
     }
 
+    #[test]
+    fn variations_in_order() {
+        // The rotations come first, then the mirror image and its rotations
+        let f = build_f_pentomino();
+        let flipped = f.flip();
+        let expected: Vec<SimplePolyomino<SimplePoint>> = (0..4).map(|i| f.turn(i))
+            .chain((0..4).map(|i| flipped.turn(i)))
+            .collect();
+
+        assert_eq!(f.make_rotations(), expected[..4].to_vec());
+        assert_eq!(f.make_all_variations(), expected);
+
+        // Repeats are left out where they first turn up again
+        let i = build_i_pentomino();
+        assert_eq!(i.make_all_variations(), vec![i.clone(), i.turn(1)]);
+    }
+
     #[test]
     fn count() {
         assert_eq!(build_f_pentomino().iter().count(), 5);
@@ -325,20 +587,27 @@ mod tests {
     fn variations_v() {
         assert_eq!(build_v_pentomino().make_all_variations().len(), 4);
     }
-}
-
-
-
-
-
-
-
-
-
-
-
-
 
+    #[test]
+    fn canonical_f() {
+        let f = build_f_pentomino();
+        let moved = SimplePolyomino::new(f.iter().map(|p| SimplePoint::new(p.x() + 3, p.y() - 2)).collect());
 
+        assert_eq!(f.rotate().canonical_free(), f.canonical_free());
+        assert_eq!(f.flip().canonical_free(), f.canonical_free());
+        assert_eq!(moved.canonical_free(), f.canonical_free());
+        assert_eq!(f.rotate().canonical_one_sided(), f.canonical_one_sided());
+        assert_ne!(f.flip().canonical_one_sided(), f.canonical_one_sided());
+    }
 
+    #[test]
+    fn congruent() {
+        let f = build_f_pentomino();
 
+        assert!(f.is_congruent_to(&f.flip().rotate()));
+        assert!(f.is_one_sided_congruent_to(&f.rotate().rotate()));
+        assert!(!f.is_one_sided_congruent_to(&f.flip()));
+        assert!(!f.is_congruent_to(&build_v_pentomino()));
+        assert!(build_v_pentomino().is_one_sided_congruent_to(&build_v_pentomino().flip()));
+    }
+}
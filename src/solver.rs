@@ -1,32 +1,251 @@
-use bit_vec::BitVec;
+use std::mem;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Instant;
+
+use rand::rngs::Xoshiro256PlusPlus;
+use rand::seq::SliceRandom;
+use rand::RngExt;
+use rand::SeedableRng;
+use rustc_hash::FxHashSet;
 
 use crate::board::board_utils;
+use crate::board::board_utils::RegionScanner;
 use crate::board::Board;
+use crate::estimate::Estimate;
+use crate::placement::Placement;
+use crate::point::Point;
+use crate::point::SimplePoint;
 use crate::polyomino::Polyomino;
+use crate::symmetry;
+use crate::symmetry::SolutionKey;
+use crate::symmetry::SolutionKeys;
+use crate::symmetry::SymmetryCase;
+
+type RegionCheckFn<T> = dyn Fn(&Board<T>, usize) -> bool + Sync;
+pub(crate) type SolutionCallbackFn<T> = dyn Fn(&Board<T>) + Sync;
+
+// Regions up to this many times the size of the largest piece are checked by
+// the region pruning. When all the pieces are the same size, only regions
+// smaller than a piece are checked, as any other region that can't be filled
+// is found by the search almost as quickly.
+const REGION_LIMIT: usize = 1;
+
+// The cells next to a piece, relative to its origin
+fn surroundings<P:Polyomino>(p: &P) -> Vec<(i16, i16, i16)> {
+    let cells: Vec<(i16, i16, i16)> = p.iter().map(|pt| (pt.x(), pt.y(), pt.z())).collect();
+    let mut res = Vec::new();
+
+    for &(x, y, z) in &cells {
+        for adj in P::Pt::neighbours(x, y).iter().map(|&(dx, dy, dz)| (x + dx, y + dy, z + dz)) {
+            if !cells.contains(&adj) && !res.contains(&adj) {
+                res.push(adj);
+            }
+        }
+    }
+
+    res
+}
+
+// How the solver chooses what to branch on at each step of the search
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    // Fill the first empty cell, in column-major order
+    #[default]
+    FirstEmptyCell,
+    // Fill the empty cell that can be covered in the fewest ways
+    MostConstrainedCell,
+    // Place the remaining piece that fits on the board in the fewest ways
+    MostConstrainedPiece,
+}
+
+// How many copies of a piece a solution uses. A piece with no maximum can be
+// used any number of times.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Multiplicity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Multiplicity {
+    // Used exactly once, the default for every piece
+    pub const ONCE: Multiplicity = Multiplicity { min: 1, max: Some(1) };
+    // Used once or not at all
+    pub const OPTIONAL: Multiplicity = Multiplicity { min: 0, max: Some(1) };
+    // Used any number of times, including not at all
+    pub const UNLIMITED: Multiplicity = Multiplicity { min: 0, max: None };
+
+    pub fn exactly(n: usize) -> Multiplicity {
+        Multiplicity { min: n, max: Some(n) }
+    }
+
+    pub fn between(min: usize, max: usize) -> Multiplicity {
+        Multiplicity { min, max: Some(max) }
+    }
+
+    pub fn at_least(min: usize) -> Multiplicity {
+        Multiplicity { min, max: None }
+    }
+}
+
+impl Default for Multiplicity {
+    fn default() -> Multiplicity {
+        Multiplicity::ONCE
+    }
+}
+
+// How many more copies of each piece can be placed. Up to `spare[i]` of the
+// copies of piece i can be left over, so the piece is short of its minimum
+// while `left[i] > spare[i]`.
+#[derive(Clone)]
+struct PieceCounts {
+    left: Vec<usize>,
+    spare: Vec<usize>,
+    // The number of pieces with copies left, and the number that are short
+    open: usize,
+    short: usize,
+    // How many more cells can be left uncovered
+    gaps: usize,
+}
+
+impl PieceCounts {
+    fn new(multiplicities: &[Multiplicity], gaps: usize) -> PieceCounts {
+        let left: Vec<usize> = multiplicities.iter().map(|m| m.max.unwrap_or(usize::MAX)).collect();
+        let spare: Vec<usize> = multiplicities.iter().zip(&left).map(|(m, &l)| l - m.min.min(l)).collect();
+
+        PieceCounts {
+            open: left.iter().filter(|&&l| l > 0).count(),
+            short: left.iter().zip(&spare).filter(|(l, s)| l > s).count(),
+            left,
+            spare,
+            gaps,
+        }
+    }
+
+    fn take(&mut self, i: usize) {
+        if i == Placement::GAP {
+            self.gaps -= 1;
+            return;
+        }
+
+        self.left[i] -= 1;
+        if self.left[i] == 0 {
+            self.open -= 1;
+        }
+        if self.left[i] == self.spare[i] {
+            self.short -= 1;
+        }
+    }
+
+    fn put_back(&mut self, i: usize) {
+        if i == Placement::GAP {
+            self.gaps += 1;
+            return;
+        }
 
-type RegionCheckFn<T> = dyn Fn(&Board<T>, usize) -> bool;
-type SolutionCallbackFn<T> = dyn Fn(&Board<T>);
+        if self.left[i] == self.spare[i] {
+            self.short += 1;
+        }
+        if self.left[i] == 0 {
+            self.open += 1;
+        }
+        self.left[i] += 1;
+    }
+
+    // The indexes of the pieces that can still be placed
+    fn available(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.left.len()).filter(|&i| self.left[i] > 0)
+    }
 
-pub struct Solver<'a, P:Polyomino> {
-    board: &'a mut Board<'a, P>,
+    // True if piece i has one copy left, and it has to be used
+    fn last_required(&self, i: usize) -> bool {
+        self.left[i] == 1 && self.spare[i] == 0
+    }
+
+    fn is_empty(&self) -> bool {
+        self.open == 0
+    }
+
+    // True if every piece has been used at least as often as it has to be
+    fn complete(&self) -> bool {
+        self.short == 0
+    }
+}
+
+pub struct Solver<'a, 'b, P:Polyomino> {
+    board: &'b mut Board<'a, P>,
     candidates: &'a [Vec<P>],
+    // The indexes of the variations of each candidate that are tried, which
+    // are fewer than all of them while the board's symmetry is broken
+    variations: Vec<Vec<usize>>,
     region_check: Option<&'a RegionCheckFn<P>>,
     callback_each_solution: Option<&'a SolutionCallbackFn<P>>,
     solutions: Vec<Board<'a, P>>,
     enumerate_solutions: bool,
     num_solutions: u32,
+    num_raw_solutions: u32,
+    distinct: bool,
+    keys: Option<SolutionKeys>,
+    orbits: Option<SolutionKeys>,
+    seen: FxHashSet<SolutionKey>,
+    found_keys: Option<Vec<(SolutionKey, u32)>>,
+    region_pruning: bool,
+    piece_sizes: Vec<usize>,
+    uniform_size: Option<usize>,
+    exact_fill: bool,
+    region_limit: usize,
+    region_scanner: RegionScanner,
+    surroundings: Vec<Vec<Vec<(i16, i16, i16)>>>,
+    region_sizes: Vec<usize>,
+    threads: usize,
+    split_depth: usize,
+    strategy: Strategy,
+    spare_branches: Vec<Vec<Placement>>,
+    multiplicities: Vec<Multiplicity>,
+    max_uncovered: usize,
+    empty_cells: usize,
+    seed: Option<u64>,
+    rng: Option<Xoshiro256PlusPlus>,
 }
 
-impl<'a, P:Polyomino> Solver<'a, P> {
-    pub fn new(b: &'a mut Board<'a, P>, c: &'a [Vec<P>]) -> Solver<'a, P> {
+impl<'a, 'b, P:Polyomino> Solver<'a, 'b, P> {
+    pub fn new(b: &'b mut Board<'a, P>, c: &'a [Vec<P>]) -> Solver<'a, 'b, P> {
+        let piece_sizes: Vec<usize> = c.iter().map(|v| v.first().map_or(0, |p| p.iter().count())).collect();
+        let empty_cells = board_utils::count_unoccupied(b);
+
         Solver {
             board: b,
             candidates: c,
+            variations: SymmetryCase::all(c).variations,
             region_check: None,
             callback_each_solution: None,
             solutions: Vec::new(),
             enumerate_solutions: false,
             num_solutions: 0,
+            num_raw_solutions: 0,
+            distinct: false,
+            keys: None,
+            orbits: None,
+            seen: FxHashSet::default(),
+            found_keys: None,
+            region_pruning: true,
+            piece_sizes,
+            uniform_size: None,
+            exact_fill: false,
+            region_limit: 0,
+            region_scanner: RegionScanner::new(),
+            surroundings: c.iter().map(|vs| vs.iter().map(surroundings).collect()).collect(),
+            region_sizes: Vec::new(),
+            threads: 1,
+            split_depth: 2,
+            strategy: Strategy::default(),
+            spare_branches: Vec::new(),
+            multiplicities: vec![Multiplicity::ONCE; c.len()],
+            max_uncovered: 0,
+            empty_cells,
+            seed: None,
+            rng: None,
         }
     }
 
@@ -37,27 +256,237 @@ impl<'a, P:Polyomino> Solver<'a, P> {
     pub fn set_callback_function(&mut self, cb: &'a SolutionCallbackFn<P>) {
         self.callback_each_solution = Some(cb);
     }
+
+    // After each piece is placed, check that every empty region of the board
+    // could be exactly filled by some of the remaining pieces. This is on by
+    // default and is only used when the remaining pieces can cover the empty
+    // part of the board.
+    pub fn set_region_pruning(&mut self, prune: bool) {
+        self.region_pruning = prune;
+    }
+
+    // Search with this many threads. The search tree is cut at the split depth
+    // (see set_split_depth) and the subtrees below it are handed out to the
+    // threads. Solutions are returned in the same order as a single threaded
+    // search, but the callback function may be called from any thread and in
+    // any order.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    // The number of pieces placed before the search is split into independent
+    // subproblems. Deeper splits give more, smaller, subproblems.
+    pub fn set_split_depth(&mut self, depth: usize) {
+        self.split_depth = depth;
+    }
                                  
+    // What to branch on at each step of the search. The default fills the
+    // empty cells in order. The other strategies spend longer on each step but
+    // find dead ends, such as a corner no piece can reach, much sooner. They
+    // are only used when the pieces can cover the whole board.
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        self.strategy = strategy;
+    }
+
+    // How many copies of candidate `i` a solution uses. Every piece is used
+    // exactly once unless this says otherwise. A solution either covers the
+    // whole board or uses the most copies allowed of every piece.
+    pub fn set_multiplicity(&mut self, i: usize, multiplicity: Multiplicity) {
+        self.multiplicities[i] = multiplicity;
+    }
+
+    // Lets a solution leave up to this many of the empty cells uncovered. They
+    // are shown as reserved cells. Cells that must stay uncovered are reserved
+    // on the board before the search instead (see Board::reserve).
+    pub fn set_max_uncovered(&mut self, cells: usize) {
+        self.max_uncovered = cells;
+    }
+
+    // Leave out solutions that are a rotation or reflection of another, under
+    // the symmetries of the puzzle (see symmetry::puzzle_symmetries), and
+    // solutions that only swap around pieces that are copies of each other.
+    // Each class of solutions is then counted and returned once, and
+    // raw_count gives the number of solutions counting every rotation and
+    // reflection. The symmetry is broken by fixing the orientations of some
+    // of the pieces (see symmetry::reduce_by_symmetry), so most of the
+    // solutions that are left out are never searched for.
+    pub fn set_distinct(&mut self, distinct: bool) {
+        self.distinct = distinct;
+    }
+
+    // Try the placements at each step of the search in a random order, made
+    // from the seed, instead of piece by piece and variation by variation.
+    // The first solution is then a random one, though not every solution is
+    // equally likely, and it is the same one every time for the same seed
+    // and number of threads. Counts are not changed.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
     pub fn count_solutions(&mut self) -> u32 {
-        let mut usable_candidates = BitVec::from_elem(self.candidates.len(), true);
-        
-        self.solve_ex(&mut usable_candidates);
+        self.run();
         
         self.num_solutions
     }
-    
-    pub fn solve(&'a mut self) -> &'a Vec<Board<'a, P>> {
-        let mut usable_candidates = BitVec::from_elem(self.candidates.len(), true);
 
+    // The number of solutions found, including the rotations and reflections
+    // of each one left out by set_distinct
+    pub fn raw_count(&self) -> u32 {
+        self.num_raw_solutions
+    }
+    
+    pub fn solve(&mut self) -> &Vec<Board<'a, P>> {
         self.enumerate_solutions = true;
         
-        self.solve_ex(&mut usable_candidates);
+        self.run();
 
         &self.solutions
     }
 
-    fn solve_ex(&mut self, usable_candidates: &mut BitVec) {
-        if usable_candidates.none() {
+    // Produces the solutions one at a time, in the same order as solve(). The
+    // search only runs as far as needed to produce the next solution, so
+    // dropping the iterator (or using take()) stops it early. The board is left
+    // as it was found once the iterator is finished or dropped.
+    pub fn solutions(&mut self) -> Solutions<'_, 'a, 'b, P> {
+        let mut cases = self.cases();
+        cases.reverse();
+        self.start_case(cases.pop().unwrap());
+        let counts = self.prepare();
+
+        Solutions {
+            solver: self,
+            counts,
+            stack: Vec::new(),
+            started: false,
+            cases,
+        }
+    }
+
+    // Estimates how big the search is without running it, from this many
+    // random probes down the search tree (see Estimate). The probes use the
+    // seed if one was set, so that the estimate can be repeated.
+    pub fn estimate(&mut self, probes: usize) -> Estimate {
+        self.start_case(SymmetryCase::all(self.candidates));
+        let mut counts = self.prepare();
+        let mut rng = self.rng.take().unwrap_or_else(|| Xoshiro256PlusPlus::seed_from_u64(rand::random()));
+        let mut nodes = Vec::with_capacity(probes);
+        let mut solutions = Vec::with_capacity(probes);
+        let mut seconds = Vec::with_capacity(probes);
+
+        for _ in 0..probes {
+            let (n, s, t) = self.probe(&mut counts, &mut rng);
+            nodes.push(n);
+            solutions.push(s);
+            seconds.push(t);
+        }
+
+        Estimate::new(&nodes, &solutions, &seconds)
+    }
+
+    fn run(&mut self) {
+        for case in self.cases() {
+            self.start_case(case);
+            let mut counts = self.prepare();
+
+            if self.threads > 1 {
+                self.solve_parallel(&mut counts);
+            } else {
+                self.solve_ex(&mut counts);
+            }
+        }
+    }
+
+    // The parts the search is split into. Without set_distinct this is the
+    // whole puzzle; with it, the cases that break the board's symmetry. Only
+    // the pieces used exactly once can break it.
+    fn cases(&mut self) -> Vec<SymmetryCase> {
+        if !self.distinct {
+            self.orbits = None;
+            return vec![SymmetryCase::all(self.candidates)];
+        }
+
+        let once: Vec<bool> = self.multiplicities.iter().map(|&m| m == Multiplicity::ONCE).collect();
+        self.orbits = Some(SolutionKeys::new(self.board, self.candidates));
+        symmetry::reduce_with(self.board, self.candidates, &once)
+    }
+
+    // Searches with the variations of the case from now on. Its solutions only
+    // have to be compared if it has some symmetry left, or if some of the
+    // pieces are copies of each other.
+    fn start_case(&mut self, case: SymmetryCase) {
+        let compare = self.orbits.as_ref().is_some_and(|o| case.residual.len() > 1 || o.has_copies());
+
+        self.keys = compare.then(|| SolutionKeys::with_symmetries(self.board, self.candidates, &case.residual));
+        self.variations = case.variations;
+    }
+
+    // The copies of each piece that are left once the pieces already on the
+    // board are taken out, and what they mean for the pruning
+    fn prepare(&mut self) -> PieceCounts {
+        self.rng = self.seed.map(Xoshiro256PlusPlus::seed_from_u64);
+
+        self.seen.clear();
+
+        let mut counts = PieceCounts::new(&self.multiplicities, self.max_uncovered);
+        for i in board_utils::fixed_candidates(self.board, self.candidates) {
+            if counts.left[i] > 0 {
+                counts.take(i);
+            }
+        }
+
+        // If the pieces left can cover the board, apart from the cells that may
+        // be left uncovered, every solution does
+        let area = counts.available()
+            .fold(self.max_uncovered, |a, i| a.saturating_add(self.piece_sizes[i].saturating_mul(counts.left[i])));
+        self.exact_fill = area >= self.empty_cells;
+
+        // A candidate without variations can't be placed, so it has no size
+        let sizes: Vec<usize> = counts.available().map(|i| self.piece_sizes[i]).filter(|&s| s > 0).collect();
+        let largest_piece = sizes.iter().copied().max().unwrap_or(0);
+        self.uniform_size = sizes.first().copied().filter(|&s| sizes.iter().all(|&t| t == s));
+        self.region_limit = self.uniform_size.map_or(REGION_LIMIT * largest_piece, |s| s - 1);
+
+        counts
+    }
+
+    // True once the board is covered or there is nothing left to place
+    fn is_finished(&self, counts: &PieceCounts) -> bool {
+        self.empty_cells == 0 || counts.is_empty()
+    }
+
+    // True if a finished board is a solution: every piece has been used often
+    // enough, and no more cells are left uncovered than allowed
+    fn is_solution(&self, counts: &PieceCounts) -> bool {
+        counts.complete() && (!self.exact_fill || self.empty_cells <= counts.gaps)
+    }
+
+    // Counts a solution that is on the board, and returns true unless it is
+    // the same as one found before
+    fn record_solution(&mut self) -> bool {
+        let key = self.keys.as_ref().map(|keys| keys.key(self.board));
+        if key.as_ref().is_some_and(|key| self.seen.contains(key)) {
+            return false;
+        }
+
+        let orbit = self.orbits.as_ref().map_or(1, |orbits| orbits.orbit_size(self.board) as u32);
+        self.num_raw_solutions += orbit;
+
+        if let Some(key) = key {
+            if let Some(found) = &mut self.found_keys {
+                found.push((key.clone(), orbit));
+            }
+            self.seen.insert(key);
+        }
+
+        true
+    }
+
+    fn solve_ex(&mut self, counts: &mut PieceCounts) {
+        if self.is_finished(counts) {
+            if !self.is_solution(counts) || !self.record_solution() {
+                return;
+            }
+
             self.num_solutions += 1;
 
             if self.enumerate_solutions {
@@ -71,40 +500,1079 @@ impl<'a, P:Polyomino> Solver<'a, P> {
             return;
         }
 
-        if let Some(fit_point) = board_utils::get_first_unoccupied(self.board) {
-            if let Some(region_check_fn) = self.region_check {
-                if !region_check_fn(
+        let mut branches = self.spare_branches.pop().unwrap_or_default();
+        self.find_branches(counts, &mut branches);
+        self.shuffle(&mut branches);
+
+        for placement in &branches {
+            if self.place(placement) {
+                counts.take(placement.candidate);
+                if !self.isolates_unfillable_region(counts, placement) {
+                    self.solve_ex(counts);
+                }
+                counts.put_back(placement.candidate);
+                self.unplace(placement);
+            }
+        }
+
+        branches.clear();
+        self.spare_branches.push(branches);
+    }
+
+    fn place(&mut self, placement: &Placement) -> bool {
+        if placement.candidate == Placement::GAP {
+            let reserved = self.board.reserve(&placement.anchor);
+            if reserved {
+                self.empty_cells -= 1;
+            }
+
+            return reserved;
+        }
+
+        let placed = self.board.add_polyomino(&self.candidates[placement.candidate][placement.variation], &placement.anchor);
+        if placed {
+            self.empty_cells -= self.piece_sizes[placement.candidate];
+        }
+
+        placed
+    }
+
+    fn unplace(&mut self, placement: &Placement) {
+        if placement.candidate == Placement::GAP {
+            self.board.release(&placement.anchor);
+            self.empty_cells += 1;
+            return;
+        }
+
+        if let Some(first) = self.candidates[placement.candidate][placement.variation].iter().next() {
+            let anchor = placement.anchor;
+            self.board.remove_polyomino(&SimplePoint::new_3d(anchor.x + first.x(), anchor.y + first.y(), anchor.z + first.z()));
+            self.empty_cells += self.piece_sizes[placement.candidate];
+        }
+    }
+
+    // Works out the placements to try next, as set by the strategy. If there
+    // are none, this branch of the search is a dead end.
+    fn find_branches(&self, counts: &PieceCounts, branches: &mut Vec<Placement>) {
+        if self.is_dead_end() {
+            return;
+        }
+
+        match self.strategy {
+            Strategy::MostConstrainedCell if self.exact_fill => self.most_constrained_cell(counts, branches),
+            Strategy::MostConstrainedPiece if self.exact_fill => self.most_constrained_piece(counts, branches),
+            _ => self.first_empty_cell(counts, branches),
+        }
+    }
+
+    // Every variation of every remaining piece that fits with its first point
+    // at the first unoccupied point, as fit_at does, and leaving the point
+    // uncovered if that is still allowed
+    fn first_empty_cell(&self, counts: &PieceCounts, branches: &mut Vec<Placement>) {
+        let Some(fit_point) = board_utils::get_first_unoccupied(self.board) else {
+            panic!("Pieces left over, but no unoccupied points");
+        };
+
+        for i in counts.available() {
+            for &v in &self.variations[i] {
+                let poly = &self.candidates[i][v];
+                if let Some(first) = poly.iter().next() {
+                    let anchor = SimplePoint::new_3d(fit_point.x - first.x(), fit_point.y - first.y(), fit_point.z - first.z());
+                    if first.x() <= fit_point.x && first.y() <= fit_point.y && first.z() <= fit_point.z && self.board.can_add_polyomino(poly, &anchor) {
+                        branches.push(Placement { candidate: i, variation: v, anchor });
+                    }
+                }
+            }
+        }
+
+        if counts.gaps > 0 {
+            branches.push(Placement::gap(fit_point));
+        }
+    }
+
+    // The placements covering the empty cell that can be covered in the fewest
+    // ways, and leaving it uncovered if that is still allowed. Counting stops
+    // as soon as a cell is no better than the best so far.
+    fn most_constrained_cell(&self, counts: &PieceCounts, branches: &mut Vec<Placement>) {
+        let mut covering = Vec::new();
+        let mut found = None;
+
+        for cell in board_utils::get_all_unoccupied(self.board) {
+            covering.clear();
+
+            'count: for i in counts.available() {
+                for &v in &self.variations[i] {
+                    let poly = &self.candidates[i][v];
+                    for pt in poly.iter() {
+                        let anchor = SimplePoint::new_3d(cell.x - pt.x(), cell.y - pt.y(), cell.z - pt.z());
+                        if self.board.can_add_polyomino(poly, &anchor) {
+                            covering.push(Placement { candidate: i, variation: v, anchor });
+                            if found.is_some() && covering.len() >= branches.len() {
+                                break 'count;
+                            }
+                        }
+                    }
+                }
+            }
+
+            if found.is_none() || covering.len() < branches.len() {
+                mem::swap(branches, &mut covering);
+                found = Some(cell);
+
+                if branches.len() <= 1 {
+                    break;
+                }
+            }
+        }
+
+        if let Some(cell) = found.filter(|_| counts.gaps > 0) {
+            branches.push(Placement::gap(cell));
+        }
+    }
+
+    // Every position of the remaining piece that fits on the board in the
+    // fewest ways. Only a piece whose last copy has to be used is sure to go
+    // in exactly one of its positions, so if there is no such piece this
+    // branches on a cell instead.
+    fn most_constrained_piece(&self, counts: &PieceCounts, branches: &mut Vec<Placement>) {
+        let empty = board_utils::get_all_unoccupied(self.board);
+        let mut positions = Vec::new();
+        let mut found = false;
+
+        for i in counts.available().filter(|&i| counts.last_required(i)) {
+            positions.clear();
+
+            // Each position puts the piece's first point on a different empty cell
+            'count: for &v in &self.variations[i] {
+                let poly = &self.candidates[i][v];
+                let Some(first) = poly.iter().next() else {
+                    continue;
+                };
+
+                for cell in &empty {
+                    let anchor = SimplePoint::new_3d(cell.x - first.x(), cell.y - first.y(), cell.z - first.z());
+                    if self.board.can_add_polyomino(poly, &anchor) {
+                        positions.push(Placement { candidate: i, variation: v, anchor });
+                        if found && positions.len() >= branches.len() {
+                            break 'count;
+                        }
+                    }
+                }
+            }
+
+            if !found || positions.len() < branches.len() {
+                mem::swap(branches, &mut positions);
+                found = true;
+
+                if branches.len() <= 1 {
+                    return;
+                }
+            }
+        }
+
+        if !found {
+            self.most_constrained_cell(counts, branches);
+        }
+    }
+
+    // Puts the placements in a random order if there is a seed
+    fn shuffle(&mut self, branches: &mut [Placement]) {
+        if let Some(rng) = &mut self.rng {
+            branches.shuffle(rng);
+        }
+    }
+
+    // True if the caller's region check rejects the board
+    fn is_dead_end(&self) -> bool {
+        if let Some(region_check_fn) = self.region_check {
+            if let Some(fit_point) = board_utils::get_first_unoccupied(self.board) {
+                return !region_check_fn(
                     self.board,
                     board_utils::get_all_adjacent(fit_point, self.board).len(),
-                ) {
-                    return;
+                );
+            }
+        }
+
+        false
+    }
+
+    // True if the piece that was just placed has closed off a region of the
+    // board that can't be exactly filled with the remaining pieces. Only the
+    // regions next to the new piece are measured. One further away can also
+    // become unfillable when the last piece of a size is used, but it is left
+    // for the search to find once it gets there, as measuring every region
+    // each time made the Chinese puzzle about a third slower. Regions that
+    // are too large to be worth measuring are assumed to be fine.
+    fn isolates_unfillable_region(&mut self, counts: &PieceCounts, placement: &Placement) -> bool {
+        if !self.region_pruning || !self.exact_fill || self.is_finished(counts) {
+            return false;
+        }
+
+        // A cell left uncovered closes off regions just as a piece does
+        let SimplePoint { x, y, z } = placement.anchor;
+        let next_to: &[(i16, i16, i16)] = match placement.candidate {
+            Placement::GAP => P::Pt::neighbours(x, y),
+            i => &self.surroundings[i][placement.variation],
+        };
+        let starts = next_to.iter().map(|&(dx, dy, dz)| SimplePoint::new_3d(x + dx, y + dy, z + dz));
+
+        let mut regions = mem::take(&mut self.region_sizes);
+        regions.clear();
+        self.region_scanner.get_region_sizes(self.board, starts, self.region_limit, &mut regions);
+
+        let unfillable = !regions.is_empty() && match self.uniform_size {
+            Some(size) => regions.iter().map(|r| r % size).sum::<usize>() > counts.gaps,
+            None => !self.can_fill_all(counts, &regions),
+        };
+
+        self.region_sizes = regions;
+        unfillable
+    }
+
+    // Checks that each of the region sizes is the total size of some of the
+    // remaining pieces, give or take the cells that can be left uncovered
+    fn can_fill_all(&self, counts: &PieceCounts, regions: &[usize]) -> bool {
+        let largest = regions.iter().copied().max().unwrap_or(0);
+
+        // The number of remaining pieces of each size, up to as many as fit in
+        // the largest region
+        let mut sizes: Vec<(usize, usize)> = Vec::new();
+        for i in counts.available() {
+            let size = self.piece_sizes[i];
+            let copies = counts.left[i].min(largest / size.max(1));
+            match sizes.iter_mut().find(|s| s.0 == size) {
+                Some(s) => s.1 += copies,
+                None => sizes.push((size, copies)),
+            }
+        }
+
+        // Work out which areas can be made from the remaining pieces. Several
+        // pieces of the same size are combined in powers of two, so each size
+        // needs only a few passes
+        let mut reachable = vec![false; largest + 1];
+        reachable[0] = true;
+
+        for (size, mut count) in sizes {
+            let mut multiple = 1;
+            while count > 0 && size <= largest {
+                let area = size * multiple.min(count);
+                for total in (area..=largest).rev() {
+                    reachable[total] |= reachable[total - area];
+                }
+                count -= multiple.min(count);
+                multiple *= 2;
+            }
+        }
+
+        // The fewest cells each region has to leave uncovered
+        let mut gaps_needed = 0;
+        for &r in regions {
+            match (0..=r.min(counts.gaps)).find(|&g| reachable[r - g]) {
+                Some(g) => gaps_needed += g,
+                None => return false,
+            }
+        }
+
+        gaps_needed <= counts.gaps
+    }
+
+    // Walks the search tree exactly like solve_ex, but stops `depth` pieces
+    // down and records the placements that lead to each remaining subtree.
+    fn split(&mut self, depth: usize, counts: &mut PieceCounts,
+             prefix: &mut Vec<Placement>, subproblems: &mut Vec<Vec<Placement>>) {
+        if depth == 0 || self.is_finished(counts) {
+            subproblems.push(prefix.clone());
+            return;
+        }
+
+        let mut branches = Vec::new();
+        self.find_branches(counts, &mut branches);
+        self.shuffle(&mut branches);
+
+        for placement in &branches {
+            if self.place(placement) {
+                counts.take(placement.candidate);
+                prefix.push(*placement);
+                if !self.isolates_unfillable_region(counts, placement) {
+                    self.split(depth - 1, counts, prefix, subproblems);
+                }
+                prefix.pop();
+                counts.put_back(placement.candidate);
+                self.unplace(placement);
+            }
+        }
+    }
+
+    // Goes down the search tree from the board as it is, taking a random
+    // branch at each step. At each node this does what solve_ex does, working
+    // out the branches and trying each one, so it takes about as long. Returns
+    // the estimated number of nodes and solutions, and the estimated time in
+    // seconds.
+    fn probe(&mut self, counts: &mut PieceCounts, rng: &mut Xoshiro256PlusPlus) -> (f64, f64, f64) {
+        let mut path = Vec::new();
+        let mut branches = Vec::new();
+        let mut children = Vec::new();
+
+        // The number of nodes at the depth of the probe, if every node above
+        // it had as many children as the ones it went through
+        let mut width = 1.0;
+        let mut nodes = 1.0;
+        let mut solutions = 0.0;
+        let mut seconds = 0.0;
+
+        loop {
+            let start = Instant::now();
+            if self.is_finished(counts) {
+                if self.is_solution(counts) {
+                    solutions = width;
+                }
+                break;
+            }
+
+            branches.clear();
+            self.find_branches(counts, &mut branches);
+
+            children.clear();
+            for placement in &branches {
+                if self.place(placement) {
+                    counts.take(placement.candidate);
+                    if !self.isolates_unfillable_region(counts, placement) {
+                        children.push(*placement);
+                    }
+                    counts.put_back(placement.candidate);
+                    self.unplace(placement);
                 }
             }
 
-            for i in 0..usable_candidates.len() {
-                if usable_candidates.get(i) == Some(true) {
-                    for poly in &self.candidates[i] {
-                        if board_utils::fit_at(self.board, poly, &fit_point) {
-                            usable_candidates.set(i, false);
-                            self.solve_ex(usable_candidates);
-                            usable_candidates.set(i, true);
-                            self.board.remove_polyomino(&fit_point);
+            seconds += width * start.elapsed().as_secs_f64();
+            if children.is_empty() {
+                break;
+            }
+
+            width *= children.len() as f64;
+            nodes += width;
+
+            let placement = children[rng.random_range(0..children.len())];
+            self.place(&placement);
+            counts.take(placement.candidate);
+            path.push(placement);
+        }
+
+        for placement in path.iter().rev() {
+            counts.put_back(placement.candidate);
+            self.unplace(placement);
+        }
+
+        (nodes, solutions, seconds)
+    }
+
+    fn solve_parallel(&mut self, counts: &mut PieceCounts) {
+        let mut subproblems = Vec::new();
+        self.split(self.split_depth, counts, &mut Vec::new(), &mut subproblems);
+
+        let next_subproblem = AtomicUsize::new(0);
+        let this = &*self;
+        let counts = &*counts;
+
+        let mut results: Vec<Finished<'a, P>> = thread::scope(|s| {
+            let workers: Vec<_> = (0..this.threads)
+                .map(|_| s.spawn(|| {
+                    let mut finished = Vec::new();
+
+                    loop {
+                        let idx = next_subproblem.fetch_add(1, Ordering::Relaxed);
+                        if idx >= subproblems.len() {
+                            return finished;
                         }
+
+                        let mut board = this.board.clone();
+                        let mut counts = counts.clone();
+                        let mut sub = Solver::new(&mut board, this.candidates);
+                        sub.variations.clone_from(&this.variations);
+                        sub.orbits.clone_from(&this.orbits);
+                        sub.region_check = this.region_check;
+                        sub.region_pruning = this.region_pruning;
+                        sub.strategy = this.strategy;
+                        sub.callback_each_solution = this.callback_each_solution;
+                        sub.enumerate_solutions = this.enumerate_solutions;
+                        sub.exact_fill = this.exact_fill;
+                        sub.uniform_size = this.uniform_size;
+                        sub.region_limit = this.region_limit;
+                        sub.rng = this.seed.map(|seed| Xoshiro256PlusPlus::seed_from_u64(seed.wrapping_add(idx as u64)));
+
+                        // A solution that is new to this subproblem may have been found
+                        // in another one, so they are only reported once the results are
+                        // put together
+                        if this.keys.is_some() {
+                            sub.keys.clone_from(&this.keys);
+                            sub.found_keys = Some(Vec::new());
+                            sub.enumerate_solutions = this.enumerate_solutions || this.callback_each_solution.is_some();
+                            sub.callback_each_solution = None;
+                        }
+
+                        for placement in &subproblems[idx] {
+                            sub.place(placement);
+                            counts.take(placement.candidate);
+                        }
+
+                        sub.solve_ex(&mut counts);
+
+                        finished.push(Finished {
+                            idx,
+                            num_solutions: sub.num_solutions,
+                            num_raw_solutions: sub.num_raw_solutions,
+                            solutions: mem::take(&mut sub.solutions),
+                            keys: sub.found_keys.take(),
+                        });
+                    }
+                }))
+                .collect();
+
+            workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+        });
+
+        results.sort_by_key(|r| r.idx);
+
+        for result in results {
+            let Some(keys) = result.keys else {
+                self.num_solutions += result.num_solutions;
+                self.num_raw_solutions += result.num_raw_solutions;
+                self.solutions.extend(result.solutions);
+                continue;
+            };
+
+            let mut solutions = result.solutions.into_iter();
+            for (key, orbit) in keys {
+                let solution = solutions.next();
+                if !self.seen.insert(key) {
+                    continue;
+                }
+
+                self.num_solutions += 1;
+                self.num_raw_solutions += orbit;
+                if let Some(solution) = solution {
+                    if let Some(cb) = self.callback_each_solution {
+                        cb(&solution);
+                    }
+                    if self.enumerate_solutions {
+                        self.solutions.push(solution);
                     }
                 }
             }
-        } else {
-            panic!("Pieces left over, but no unoccupied points");
         }
     }
 }
-    
 
+// What a worker found in one subproblem, with the keys of the solutions if
+// they are to be told apart up to symmetry
+struct Finished<'a, P: Polyomino> {
+    idx: usize,
+    num_solutions: u32,
+    num_raw_solutions: u32,
+    solutions: Vec<Board<'a, P>>,
+    keys: Option<Vec<(SolutionKey, u32)>>,
+}
+
+// One level of the search performed by Solutions: the placements to try, the
+// next one to try and the one currently on the board (if any)
+struct Frame {
+    branches: Vec<Placement>,
+    next: usize,
+    placed: Option<Placement>,
+}
+
+pub struct Solutions<'s, 'a, 'b, P:Polyomino> {
+    solver: &'s mut Solver<'a, 'b, P>,
+    counts: PieceCounts,
+    stack: Vec<Frame>,
+    started: bool,
+    // The cases of set_distinct still to search, the next one last
+    cases: Vec<SymmetryCase>,
+}
+
+impl<'s, 'a, 'b, P:Polyomino> Solutions<'s, 'a, 'b, P> {
+    fn descend(&mut self) {
+        let mut branches = Vec::new();
+        self.solver.find_branches(&self.counts, &mut branches);
+        self.solver.shuffle(&mut branches);
+
+        self.stack.push(Frame { branches, next: 0, placed: None });
+    }
 
+    // The next solution of the case being searched
+    fn next_in_case(&mut self) -> Option<Board<'a, P>> {
+        if !self.started {
+            self.started = true;
 
+            if self.solver.is_finished(&self.counts) {
+                let found = self.solver.is_solution(&self.counts) && self.solver.record_solution();
+                return found.then(|| self.solver.board.clone());
+            }
 
+            self.descend();
+        }
 
+        while let Some(frame) = self.stack.last_mut() {
+            if let Some(placement) = frame.placed.take() {
+                self.solver.unplace(&placement);
+                self.counts.put_back(placement.candidate);
+            }
 
+            while frame.next < frame.branches.len() && frame.placed.is_none() {
+                let placement = frame.branches[frame.next];
+                frame.next += 1;
+
+                if self.solver.place(&placement) {
+                    frame.placed = Some(placement);
+                }
+            }
 
+            match frame.placed {
+                Some(placement) => {
+                    self.counts.take(placement.candidate);
+
+                    if self.solver.is_finished(&self.counts) {
+                        if self.solver.is_solution(&self.counts) && self.solver.record_solution() {
+                            return Some(self.solver.board.clone());
+                        }
+                    } else if !self.solver.isolates_unfillable_region(&self.counts, &placement) {
+                        self.descend();
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl<'s, 'a, 'b, P:Polyomino> Iterator for Solutions<'s, 'a, 'b, P> {
+    type Item = Board<'a, P>;
+
+    fn next(&mut self) -> Option<Board<'a, P>> {
+        loop {
+            if let Some(solution) = self.next_in_case() {
+                return Some(solution);
+            }
+
+            let case = self.cases.pop()?;
+            self.solver.start_case(case);
+            self.counts = self.solver.prepare();
+            self.started = false;
+        }
+    }
+}
+
+impl<'s, 'a, 'b, P:Polyomino> Drop for Solutions<'s, 'a, 'b, P> {
+    fn drop(&mut self) {
+        for frame in &self.stack {
+            if let Some(placement) = &frame.placed {
+                self.solver.unplace(placement);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::board_utils;
+    use crate::board::Board;
+    use crate::board::BoardState;
+    use crate::dlx::DlxSolver;
+    use crate::point::CubePoint;
+    use crate::point::HexPoint;
+    use crate::point::Point;
+    use crate::point::SimplePoint;
+    use crate::point::TrianglePoint;
+    use crate::polyomino::Polyomino;
+    use crate::polyomino::SimplePolyomino;
+    use crate::solver::Multiplicity;
+    use crate::solver::Solver;
+    use crate::solver::Strategy;
+    use crate::utils;
+    use crate::utils::Orientations;
+    use crate::utils::PredefinedPolyominoes;
+    use crate::utils::Restrictions;
+
+    fn pentominoes() -> Vec<SimplePolyomino<SimplePoint>> {
+        utils::get_polyominoes(PredefinedPolyominoes::Pentominoes).unwrap()
+    }
+
+    #[test]
+    fn parallel_matches_serial() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        let expected: Vec<String> = solver.solve().iter().map(|s| s.to_string()).collect();
+
+        for depth in 0..4 {
+            let mut b = Board::new(20, 3);
+            let mut solver = Solver::new(&mut b, &polys);
+            solver.set_threads(4);
+            solver.set_split_depth(depth);
+            let found: Vec<String> = solver.solve().iter().map(|s| s.to_string()).collect();
+
+            assert_eq!(found, expected);
+        }
+    }
 
+    #[test]
+    fn parallel_count() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_threads(3);
+        solver.set_split_depth(3);
+        assert_eq!(solver.count_solutions(), 8);
 
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_threads(3);
+        solver.set_split_depth(3);
+        solver.set_distinct(true);
+        assert_eq!(solver.count_solutions(), 2);
+        assert_eq!(solver.raw_count(), 8);
+    }
+
+    #[test]
+    fn distinct_solutions() {
+        // The 20x3 rectangle has two solutions up to symmetry, and each comes
+        // in all four orientations of the board
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_distinct(true);
+        assert_eq!(solver.count_solutions(), 2);
+        assert_eq!(solver.raw_count(), 8);
+
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_distinct(true);
+        let expected: Vec<String> = solver.solve().iter().map(|s| s.to_string()).collect();
+        assert_eq!(expected.len(), 2);
+
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_distinct(true);
+        assert_eq!(solver.solutions().map(|s| s.to_string()).collect::<Vec<String>>(), expected);
+
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_threads(4);
+        solver.set_distinct(true);
+        assert_eq!(solver.solve().iter().map(|s| s.to_string()).collect::<Vec<String>>(), expected);
+        assert_eq!(solver.raw_count(), 8);
+
+        // The board with holes in it has the symmetries of the square, and the
+        // symmetry module finds the same number by breaking them instead
+        let mut b = Board::from_file("data/b8x8holes.board").unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_threads(4);
+        solver.set_distinct(true);
+        assert_eq!(solver.count_solutions(), 21);
+        assert_eq!(solver.raw_count(), 21 * 8);
+    }
+
+    #[test]
+    fn hexiamonds() {
+        // The twelve hexiamonds fill a rhombus of 6 by 6 in 156 ways, each in
+        // the four orientations the rhombus has
+        let hexiamonds: Vec<SimplePolyomino<TrianglePoint>> = utils::get_polyominoes(PredefinedPolyominoes::Size6).unwrap();
+        let polys = utils::build_variations(&hexiamonds, Restrictions::None);
+        assert_eq!(polys.iter().map(|vs| vs.len()).sum::<usize>(), 94);
+
+        let mut b = Board::from_file("data/hexiamonds-rhombus.board").unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_threads(4);
+        solver.set_distinct(true);
+        assert_eq!(solver.count_solutions(), 156);
+        assert_eq!(solver.raw_count(), 156 * 4);
+
+        let mut b = Board::from_file("data/hexiamonds-rhombus.board").unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+        let first = solver.solutions().next().unwrap();
+        assert_eq!(board_utils::count_unoccupied(&first), 0);
+        assert!(first.to_string().starts_with("      HHHLLLBBFFFF\n"));
+    }
+
+    #[test]
+    fn tetrahexes() {
+        // The seven tetrahexes fill a parallelogram of 7 by 4 in 9 ways, each
+        // also turned half way round. Dancing links finds the same.
+        let tetrahexes: Vec<SimplePolyomino<HexPoint>> = utils::get_polyominoes(PredefinedPolyominoes::Size4).unwrap();
+        let polys = utils::build_variations(&tetrahexes, Restrictions::None);
+        assert_eq!(polys.iter().map(|vs| vs.len()).sum::<usize>(), 44);
+
+        let mut b = Board::new(7, 4);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_distinct(true);
+        assert_eq!(solver.count_solutions(), 9);
+        assert_eq!(solver.raw_count(), 18);
+
+        let mut b = Board::new(7, 4);
+        let mut solver = DlxSolver::new(&mut b, &polys);
+        assert_eq!(solver.count_solutions(), 18);
+    }
+
+    #[test]
+    fn soma_cube() {
+        // The seven Soma pieces fill a cube in 240 ways up to rotations and
+        // reflections. A reflection of a solution swaps the two screws, which
+        // are mirror images of each other, so it is a solution as well.
+        let soma: Vec<SimplePolyomino<CubePoint>> = utils::read_polyominoes_from_file("data/soma.poly").unwrap();
+        let polys = utils::build_variations(&soma, Restrictions::None);
+        assert_eq!(polys.iter().map(|vs| vs.len()).sum::<usize>(), 12 + 24 + 12 + 12 + 12 + 12 + 8);
+
+        let mut b = Board::new_box(3, 3, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_distinct(true);
+        assert_eq!(solver.count_solutions(), 240);
+        assert_eq!(solver.raw_count(), 240 * 48);
+
+        // With their mirror images allowed the screws are copies of each
+        // other, and they can both be turned the same way. Swapping them round
+        // doesn't count as a different solution when leaving out symmetries.
+        let polys = utils::build_orientations(&soma, Orientations::All);
+        let mut b = Board::new_box(3, 3, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_distinct(true);
+        assert_eq!(solver.count_solutions(), 563);
+        assert_eq!(solver.raw_count(), 563 * 48);
+    }
+
+    #[test]
+    fn random_order() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let first = |seed: Option<u64>| {
+            let mut b = Board::new(20, 3);
+            let mut solver = Solver::new(&mut b, &polys);
+            if let Some(seed) = seed {
+                solver.set_seed(seed);
+            }
+            let solution = solver.solutions().next().unwrap().to_string();
+            solution
+        };
+
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        let mut expected: Vec<String> = solver.solve().iter().map(|s| s.to_string()).collect();
+        expected.sort();
+
+        // The same seed always gives the same solution, and there are only
+        // two solutions (and their turns), so some seed finds each of them
+        assert_eq!(first(Some(7)), first(Some(7)));
+        let mut found: Vec<String> = (0..64).map(|seed| first(Some(seed))).collect();
+        found.sort();
+        found.dedup();
+        assert_eq!(found, expected);
+
+        // The search still finds every solution
+        for threads in [1, 3] {
+            let mut b = Board::new(20, 3);
+            let mut solver = Solver::new(&mut b, &polys);
+            solver.set_seed(11);
+            solver.set_threads(threads);
+            let mut solutions: Vec<String> = solver.solve().iter().map(|s| s.to_string()).collect();
+            solutions.sort();
+            assert_eq!(solutions, expected);
+        }
+    }
+
+    #[test]
+    fn estimate() {
+        // Only the I fits at each step, so every probe sees the whole tree
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let i = polys.iter().position(|vs| vs[0].name() == Some('I')).unwrap();
+        let mut b = Board::new(5, 1);
+        let mut solver = Solver::new(&mut b, &polys[i..=i]);
+        let estimate = solver.estimate(10);
+        assert_eq!((estimate.nodes.low, estimate.nodes.high), (2.0, 2.0));
+        assert_eq!((estimate.solutions.low, estimate.solutions.high), (1.0, 1.0));
+        assert_eq!(board_utils::count_unoccupied(&b), 5);
+
+        // The 18 solutions with the tetrahexes are in the interval, and the
+        // same seed gives the same estimate
+        let tetrahexes: Vec<SimplePolyomino<HexPoint>> = utils::get_polyominoes(PredefinedPolyominoes::Size4).unwrap();
+        let polys = utils::build_variations(&tetrahexes, Restrictions::None);
+        let mut b = Board::new(7, 4);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_seed(6);
+        let estimate = solver.estimate(2000);
+        assert!(estimate.solutions.low < 18.0 && 18.0 < estimate.solutions.high);
+        assert!(estimate.nodes.value > 18.0);
+        assert_eq!(solver.estimate(2000).nodes, estimate.nodes);
+        assert_eq!(solver.count_solutions(), 18);
+    }
+
+    #[test]
+    fn iterator_matches_solve() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        let expected: Vec<String> = solver.solve().iter().map(|s| s.to_string()).collect();
+
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        let found: Vec<String> = solver.solutions().map(|s| s.to_string()).collect();
+
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn iterator_stops_early() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+
+        let first: Vec<Board<SimplePolyomino<SimplePoint>>> = solver.solutions().take(1).collect();
+        assert_eq!(first.len(), 1);
+        assert!(first[0].get(0, 0) != BoardState::Empty);
+        drop(solver);
+
+        for x in b.col_range() {
+            for y in b.row_range() {
+                assert_eq!(b.get(x, y), BoardState::Empty);
+            }
+        }
+    }
+
+    #[test]
+    fn region_pruning_mixed_sizes() {
+        let polys = utils::read_polyominoes_from_file::<SimplePolyomino<SimplePoint>>("data/chinese-puzzle.poly").unwrap();
+        let polys = utils::build_variations(&polys, Restrictions::None);
+
+        let mut b = Board::from_file("data/chinese-puzzle.board").unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+        let expected: Vec<String> = solver.solutions().take(20).map(|s| s.to_string()).collect();
+
+        let mut b = Board::from_file("data/chinese-puzzle.board").unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_region_pruning(false);
+        let found: Vec<String> = solver.solutions().take(20).map(|s| s.to_string()).collect();
+
+        assert_eq!(found.len(), 20);
+        assert_eq!(found, expected);
+    }
+
+    fn sorted_solutions(w: i16, h: i16, polys: &[Vec<SimplePolyomino<SimplePoint>>], strategy: Strategy) -> Vec<String> {
+        let mut b = Board::new(w, h);
+        let mut solver = Solver::new(&mut b, polys);
+        solver.set_strategy(strategy);
+
+        let mut res: Vec<String> = solver.solutions().map(|s| s.to_string()).collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn strategies_find_same_solutions() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let expected = sorted_solutions(20, 3, &polys, Strategy::FirstEmptyCell);
+
+        assert_eq!(sorted_solutions(20, 3, &polys, Strategy::MostConstrainedCell), expected);
+
+        // Branching on pieces is slow on a long thin board, so it gets a smaller
+        // puzzle: the L, Y, P and W pentominoes in a 5x4 rectangle
+        let polys = utils::read_polyomino_string("X\nX\nX\nXX\n\nX\nXX\nX\nX\n\nXX\nXX\nX\n\nX\nXX\n XX\n").unwrap();
+        let polys = utils::build_variations(&polys, Restrictions::None);
+        let expected = sorted_solutions(5, 4, &polys, Strategy::FirstEmptyCell);
+
+        assert_eq!(expected.len(), 20);
+        assert_eq!(sorted_solutions(5, 4, &polys, Strategy::MostConstrainedCell), expected);
+        assert_eq!(sorted_solutions(5, 4, &polys, Strategy::MostConstrainedPiece), expected);
+    }
+
+    #[test]
+    fn most_constrained_cell_parallel() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let mut b = Board::new(20, 3);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_strategy(Strategy::MostConstrainedCell);
+        solver.set_threads(2);
+        solver.set_distinct(true);
+
+        assert_eq!(solver.count_solutions(), 2);
+    }
+
+    #[test]
+    fn fixed_piece() {
+        let polys = utils::build_variations(&pentominoes(), Restrictions::None);
+        let mut b = Board::from_string("XXXaaaaaXXXXXXXXXXXX\nXXXXXXXXXXXXXXXXXXXX\nXXXXXXXXXXXXXXXXXXXX\n").unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+
+        // The I pentomino is already in place, so only the other pieces are used
+        let solutions = solver.solve();
+        assert_eq!(solutions.len(), 2);
+        assert!(solutions.iter().all(|s| s.get(3, 0) == BoardState::Fixed('a')));
+
+        let mut b = Board::from_string("XXXaaaaaXXXXXXXXXXXX\nXXXXXXXXXXXXXXXXXXXX\nXXXXXXXXXXXXXXXXXXXX\n").unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+        assert_eq!(solver.solutions().count(), 2);
+    }
+
+    #[test]
+    fn unlimited_copies() {
+        let domino = utils::read_polyomino_string::<SimplePolyomino<SimplePoint>>("XX\n").unwrap();
+        let polys = utils::build_variations(&domino, Restrictions::None);
+
+        // There are 5 ways to tile a 2x4 rectangle with dominoes
+        for threads in [1, 2] {
+            let mut b = Board::new(4, 2);
+            let mut solver = Solver::new(&mut b, &polys);
+            solver.set_multiplicity(0, Multiplicity::UNLIMITED);
+            solver.set_threads(threads);
+
+            assert_eq!(solver.count_solutions(), 5);
+        }
+
+        let mut b = Board::new(4, 2);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_multiplicity(0, Multiplicity::exactly(4));
+        assert_eq!(solver.solutions().count(), 5);
+    }
+
+    #[test]
+    fn empty_candidate() {
+        // A candidate with no variations is never placed, and doesn't upset
+        // the pruning
+        let domino = utils::read_polyomino_string::<SimplePolyomino<SimplePoint>>("XX\n").unwrap();
+        let polys = vec![Vec::new(), utils::build_variations(&domino, Restrictions::None).remove(0)];
+
+        let mut b = Board::new(4, 2);
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_multiplicity(0, Multiplicity::OPTIONAL);
+        solver.set_multiplicity(1, Multiplicity::UNLIMITED);
+        assert_eq!(solver.count_solutions(), 5);
+
+        let mut b = Board::new(4, 2);
+        let mut solver = Solver::new(&mut b, &polys[..1]);
+        assert_eq!(solver.count_solutions(), 0);
+    }
+
+    #[test]
+    fn bounded_copies() {
+        let polys = utils::read_polyomino_string::<SimplePolyomino<SimplePoint>>("XX\n\nX\n").unwrap();
+        let polys = utils::build_variations(&polys, Restrictions::None);
+        let count = |domino, monomino| {
+            let mut b = Board::new(4, 1);
+            let mut solver = Solver::new(&mut b, &polys);
+            solver.set_multiplicity(0, domino);
+            solver.set_multiplicity(1, monomino);
+            solver.count_solutions()
+        };
+
+        // A 1x4 strip can be split into dominoes and monominoes in 5 ways
+        assert_eq!(count(Multiplicity::UNLIMITED, Multiplicity::UNLIMITED), 5);
+        assert_eq!(count(Multiplicity::at_least(1), Multiplicity::UNLIMITED), 4);
+        assert_eq!(count(Multiplicity::between(0, 1), Multiplicity::UNLIMITED), 4);
+        assert_eq!(count(Multiplicity::exactly(1), Multiplicity::exactly(2)), 3);
+        assert_eq!(count(Multiplicity::exactly(1), Multiplicity::exactly(3)), 0);
+    }
+
+    #[test]
+    fn optional_pieces() {
+        let pentominoes = pentominoes();
+        let polys = utils::build_variations(&pentominoes, Restrictions::None);
+
+        // Using any 3 of the 12 pentominoes is the same as trying each set of 3
+        let mut expected = 0;
+        for a in 0..12 {
+            for b in a + 1..12 {
+                for c in b + 1..12 {
+                    let polys = utils::build_variations(&[pentominoes[a].clone(), pentominoes[b].clone(), pentominoes[c].clone()],
+                                                        Restrictions::None);
+                    let mut board = Board::new(5, 3);
+                    expected += Solver::new(&mut board, &polys).count_solutions();
+                }
+            }
+        }
+
+        for strategy in [Strategy::FirstEmptyCell, Strategy::MostConstrainedCell, Strategy::MostConstrainedPiece] {
+            let mut b = Board::new(5, 3);
+            let mut solver = Solver::new(&mut b, &polys);
+            for i in 0..polys.len() {
+                solver.set_multiplicity(i, Multiplicity::OPTIONAL);
+            }
+            solver.set_strategy(strategy);
+
+            assert!(expected > 0);
+            assert_eq!(solver.count_solutions(), expected);
+        }
+    }
+
+    #[test]
+    fn reserved_cells() {
+        let domino = utils::read_polyomino_string::<SimplePolyomino<SimplePoint>>("XX\n").unwrap();
+        let polys = utils::build_variations(&domino, Restrictions::None);
+
+        let mut b = Board::from_string("XXX\nX.X\nXXX\n").unwrap();
+        let mut solver = Solver::new(&mut b, &polys);
+        solver.set_multiplicity(0, Multiplicity::UNLIMITED);
+
+        let solutions = solver.solve();
+        assert_eq!(solutions.len(), 2);
+        assert!(solutions.iter().all(|s| s.get(1, 1) == BoardState::Reserved));
+    }
+
+    #[test]
+    fn uncovered_cells() {
+        let domino = utils::read_polyomino_string::<SimplePolyomino<SimplePoint>>("XX\n").unwrap();
+        let polys = utils::build_variations(&domino, Restrictions::None);
+
+        // The ways of tiling a 3x3 square with dominoes, leaving out up to 1 and
+        // up to 3 cells
+        for strategy in [Strategy::FirstEmptyCell, Strategy::MostConstrainedCell, Strategy::MostConstrainedPiece] {
+            for (uncovered, expected) in [(0, 0), (1, 18), (3, 74)] {
+                let mut b = Board::new(3, 3);
+                let mut solver = Solver::new(&mut b, &polys);
+                solver.set_multiplicity(0, Multiplicity::UNLIMITED);
+                solver.set_max_uncovered(uncovered);
+                solver.set_strategy(strategy);
+
+                assert_eq!(solver.solutions().count(), expected);
+            }
+        }
+
+        // Leaving one cell of a 7x3 rectangle uncovered is the same as trying
+        // each cell in turn
+        let polys = utils::read_polyomino_string::<SimplePolyomino<SimplePoint>>("X\nX\nX\nXX\n\nX\nXX\nX\nX\n\nXX\nXX\nX\n\nX\nXX\n XX\n").unwrap();
+        let polys = utils::build_variations(&polys, Restrictions::None);
+        let mut expected = 0;
+        for x in 0..7 {
+            for y in 0..3 {
+                let mut b = Board::new(7, 3);
+                b.reserve(&SimplePoint::new(x, y));
+                expected += Solver::new(&mut b, &polys).count_solutions();
+            }
+        }
+
+        for threads in [1, 2] {
+            let mut b = Board::new(7, 3);
+            let mut solver = Solver::new(&mut b, &polys);
+            solver.set_max_uncovered(1);
+            solver.set_threads(threads);
+
+            assert!(expected > 0);
+            assert_eq!(solver.count_solutions(), expected);
+        }
+
+        // Pruning with pieces of different sizes has to allow for the gaps
+        let polys = utils::read_polyomino_string::<SimplePolyomino<SimplePoint>>("XXX\n X\n\nXXXX\nX\n\nXX\nXX\nX\n\nXX\n").unwrap();
+        let polys = utils::build_variations(&polys, Restrictions::None);
+        let count = |prune| {
+            let mut b = Board::new(6, 3);
+            let mut solver = Solver::new(&mut b, &polys);
+            solver.set_max_uncovered(2);
+            solver.set_region_pruning(prune);
+            solver.count_solutions()
+        };
+
+        assert!(count(false) > 0);
+        assert_eq!(count(true), count(false));
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use rustc_hash::FxHashSet;
+
+use crate::board::board_utils;
+use crate::board::Board;
+use crate::board::BoardState;
+use crate::point::Lattice;
+use crate::point::Point;
+use crate::polyomino::Polyomino;
+
+/// The width of a square cell, the side of a triangle or the width of a
+/// hexagon, in SVG user units.
+pub const CELL: i32 = 20;
+
+/// The space around each board on a sheet, in cells.
+const MARGIN: i32 = 1;
+
+/// A picture of the board. Each piece is a polygon in its own color, cells
+/// that aren't covered are pale squares showing their labels and voids are
+/// left out.
+pub fn to_svg<P: Polyomino>(b: &Board<P>) -> String {
+    sheet(std::slice::from_ref(b), 1)
+}
+
+/// The boards laid out in rows of `columns` on one page, with a board's
+/// worth of room for each however big it is.
+pub fn sheet<P: Polyomino>(boards: &[Board<P>], columns: usize) -> String {
+    let columns = columns.max(1);
+    let rows = boards.len().div_ceil(columns);
+    let margin = (MARGIN * CELL) as f64;
+    let extents: Vec<(f64, f64)> = boards.iter().map(extent).collect();
+    let slot_w = extents.iter().map(|e| e.0).fold(0.0, f64::max) + margin;
+    let slot_h = extents.iter().map(|e| e.1).fold(0.0, f64::max) + margin;
+    let width = num(slot_w * columns.min(boards.len()) as f64 + margin);
+    let height = num(slot_h * rows as f64 + margin);
+
+    let mut svg = String::new();
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#, width, height).unwrap();
+
+    for (n, b) in boards.iter().enumerate() {
+        let x = margin + (n % columns) as f64 * slot_w;
+        let y = margin + (n / columns) as f64 * slot_h;
+
+        writeln!(svg, r#"<g transform="translate({},{})">"#, num(x), num(y)).unwrap();
+        draw_board(&mut svg, b);
+        writeln!(svg, "</g>").unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn draw_board<P: Polyomino>(svg: &mut String, b: &Board<P>) {
+    let numbers = board_utils::number_pieces(b);
+
+    // The name and cells of each piece
+    let mut names = Vec::new();
+    let mut cells: Vec<Vec<(i16, i16)>> = Vec::new();
+
+    for pt in b.points() {
+        let (x, y, z) = (pt.x, pt.y, pt.z);
+        let (dx, dy) = drawn(b, x, y, z);
+        let name = match b.get_3d(x, y, z) {
+            BoardState::Void => continue,
+            BoardState::Empty | BoardState::Reserved => {
+                if P::Pt::LATTICE == Lattice::Square || P::Pt::LATTICE == Lattice::Cubic {
+                    writeln!(svg, r##"<rect x="{}" y="{}" width="{CELL}" height="{CELL}" fill="#f4f4f4" stroke="#cccccc"/>"##,
+                             dx as i32 * CELL, dy as i32 * CELL).unwrap();
+                } else {
+                    let points: Vec<String> = corners::<P::Pt>(dx, dy).into_iter()
+                        .map(|c| { let (px, py) = position::<P::Pt>(c); format!("{},{}", num(px), num(py)) })
+                        .collect();
+                    writeln!(svg, r##"<polygon points="{}" fill="#f4f4f4" stroke="#cccccc"/>"##, points.join(" ")).unwrap();
+                }
+                if let Some(label) = b.label_3d(x, y, z) {
+                    write_label::<P::Pt>(svg, dx, dy, label);
+                }
+                continue;
+            }
+            BoardState::Full(p, ..) => p.name(),
+            BoardState::Fixed(label) => Some(label),
+        };
+
+        let n = numbers[b.to_idx(x, y, z)].unwrap();
+        if n == names.len() {
+            names.push(name);
+            cells.push(Vec::new());
+        }
+        cells[n].push((dx, dy));
+    }
+
+    for (n, (name, cells)) in names.iter().zip(&cells).enumerate() {
+        writeln!(svg, r##"<path d="{}" fill="{}" stroke="#222222" stroke-width="1.5" stroke-linejoin="round"/>"##,
+                 outline::<P::Pt>(cells), color(*name, n)).unwrap();
+    }
+}
+
+// The layers of a solid board are drawn from left to right with a gap of a
+// cell between them
+fn drawn<P: Polyomino>(b: &Board<P>, x: i16, y: i16, z: i16) -> (i16, i16) {
+    (x + z * (b.get_width() + 1), y)
+}
+
+fn write_label<T: Point>(svg: &mut String, x: i16, y: i16, label: &str) {
+    let text = label.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    let corners = corners::<T>(x, y);
+    let (cx, cy) = corners.iter()
+        .map(|&c| position::<T>(c))
+        .fold((0.0, 0.0), |a, p| (a.0 + p.0 / corners.len() as f64, a.1 + p.1 / corners.len() as f64));
+
+    writeln!(svg, r##"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="#666666">{}</text>"##,
+             num(cx), num(cy), CELL * 2 / 5, text).unwrap();
+}
+
+// The corners of a cell going clockwise. Square corners, and those of cubes
+// seen from above, are at whole cells, and the corners of triangles are at
+// every half cell across and every row down, with the triangles that point
+// up at even x + y. Hexagons stand on a corner, with their corners at every
+// half cell across and every half side down.
+fn corners<T: Point>(x: i16, y: i16) -> Vec<(i16, i16)> {
+    match T::LATTICE {
+        Lattice::Square | Lattice::Cubic => vec![(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)],
+        Lattice::Triangular if (x + y).rem_euclid(2) == 0 => vec![(x + 1, y), (x + 2, y + 1), (x, y + 1)],
+        Lattice::Triangular => vec![(x, y), (x + 2, y), (x + 1, y + 1)],
+        Lattice::Hexagonal => {
+            let (cx, cy) = (2 * x + y + 1, 3 * y + 2);
+            vec![(cx, cy - 2), (cx + 1, cy - 1), (cx + 1, cy + 1), (cx, cy + 2), (cx - 1, cy + 1), (cx - 1, cy - 1)]
+        }
+    }
+}
+
+// The width and height of the board, in SVG user units
+fn extent<P: Polyomino>(b: &Board<P>) -> (f64, f64) {
+    match P::Pt::LATTICE {
+        Lattice::Square => position::<P::Pt>((b.get_width(), b.get_height())),
+        Lattice::Triangular => position::<P::Pt>((b.get_width() + 1, b.get_height())),
+        Lattice::Hexagonal => position::<P::Pt>((2 * b.get_width() + b.get_height() - 1, 3 * b.get_height() + 1)),
+        Lattice::Cubic => position::<P::Pt>((b.get_depth() * (b.get_width() + 1) - 1, b.get_height())),
+    }
+}
+
+// Where a corner is drawn, in SVG user units
+fn position<T: Point>((x, y): (i16, i16)) -> (f64, f64) {
+    let cell = CELL as f64;
+
+    match T::LATTICE {
+        Lattice::Square | Lattice::Cubic => (x as f64 * cell, y as f64 * cell),
+        Lattice::Triangular => (x as f64 * cell / 2.0, y as f64 * cell * 3f64.sqrt() / 2.0),
+        Lattice::Hexagonal => (x as f64 * cell / 2.0, y as f64 * cell / 3f64.sqrt() / 2.0),
+    }
+}
+
+// A number for an SVG attribute, to a tenth of a unit
+fn num(v: f64) -> String {
+    format!("{}", (v * 10.0).round() / 10.0)
+}
+
+// The boundary of the cells as an SVG path. Each edge of a cell that isn't
+// shared with another cell goes clockwise around it, so the edges join up
+// into loops: clockwise around the outside and anticlockwise around holes.
+fn outline<T: Point>(cells: &[(i16, i16)]) -> String {
+    let sides: Vec<((i16, i16), (i16, i16))> = cells.iter()
+        .flat_map(|&(x, y)| {
+            let corners = corners::<T>(x, y);
+            (0..corners.len()).map(move |i| (corners[i], corners[(i + 1) % corners.len()]))
+        })
+        .collect();
+    let all: FxHashSet<((i16, i16), (i16, i16))> = sides.iter().copied().collect();
+
+    // The boundary edges leaving each corner. An edge between two of the
+    // cells is gone round both ways.
+    let mut edges: BTreeMap<(i16, i16), Vec<(i16, i16)>> = BTreeMap::new();
+    for (from, to) in sides {
+        if !all.contains(&(to, from)) {
+            edges.entry(from).or_default().push(to);
+        }
+    }
+
+    let mut path = String::new();
+    while let Some((&start, _)) = edges.iter().next() {
+        let mut corners = vec![start];
+        let mut at = start;
+
+        loop {
+            let next = edges.get_mut(&at).and_then(|out| out.pop());
+            if edges.get(&at).is_some_and(|out| out.is_empty()) {
+                edges.remove(&at);
+            }
+
+            match next {
+                Some(next) if next != start => {
+                    corners.push(next);
+                    at = next;
+                }
+                _ => break,
+            }
+        }
+
+        // Corners in the middle of a straight side are left out
+        let n = corners.len();
+        let turns = (0..n).filter(|&i| {
+            let (a, b, c) = (corners[(i + n - 1) % n], corners[i], corners[(i + 1) % n]);
+            (b.0 - a.0, b.1 - a.1) != (c.0 - b.0, c.1 - b.1)
+        }).map(|i| corners[i]);
+
+        for (i, c) in turns.enumerate() {
+            let (x, y) = position::<T>(c);
+            write!(path, "{}{} {} ", if i == 0 { "M" } else { "L" }, num(x), num(y)).unwrap();
+        }
+        path.push('Z');
+    }
+
+    path
+}
+
+// Pieces with the same name get the same color on every board of a sheet.
+// The hues are spread out by the golden angle, so that neighbouring letters
+// look different.
+fn color(name: Option<char>, n: usize) -> String {
+    let index = match name {
+        Some(c @ 'A'..='Z') => c as usize - 'A' as usize,
+        Some(c @ 'a'..='z') => c as usize - 'a' as usize + 26,
+        _ => 52 + n,
+    };
+
+    format!("hsl({:.0},65%,62%)", (index as f64 * 137.508) % 360.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::point::HexPoint;
+    use crate::point::Point;
+    use crate::point::SimplePoint;
+    use crate::point::TrianglePoint;
+    use crate::polyomino::Polyomino;
+    use crate::polyomino::SimplePolyomino;
+    use crate::svg;
+
+    #[test]
+    fn outline() {
+        // An L, and a ring of eight cells around a hole
+        assert_eq!(svg::outline::<SimplePoint>(&[(0, 0), (0, 1), (1, 1)]), "M0 0 L20 0 L20 20 L40 20 L40 40 L0 40 Z");
+
+        let ring: Vec<(i16, i16)> = (0..3).flat_map(|x| (0..3).map(move |y| (x, y))).filter(|&c| c != (1, 1)).collect();
+        assert_eq!(svg::outline::<SimplePoint>(&ring), "M0 0 L60 0 L60 60 L0 60 ZM20 20 L20 40 L40 40 L40 20 Z");
+
+        // Two triangles make a rhombus, and six make a hexagon
+        assert_eq!(svg::outline::<TrianglePoint>(&[(0, 0), (1, 0)]), "M0 17.3 L10 0 L30 0 L20 17.3 Z");
+        let hexagon: Vec<(i16, i16)> = (0..3).flat_map(|x| (0..2).map(move |y| (x, y))).collect();
+        assert_eq!(svg::outline::<TrianglePoint>(&hexagon), "M0 17.3 L10 0 L30 0 L40 17.3 L30 34.6 L10 34.6 Z");
+
+        // Two hexagons side by side, and six around a hole
+        assert_eq!(svg::outline::<HexPoint>(&[(0, 0), (1, 0)]),
+                   "M0 5.8 L10 0 L20 5.8 L30 0 L40 5.8 L40 17.3 L30 23.1 L20 17.3 L10 23.1 L0 17.3 Z");
+        let flower: Vec<(i16, i16)> = HexPoint::neighbours(1, 1).iter().map(|&(dx, dy, _)| (1 + dx, 1 + dy)).collect();
+        assert_eq!(svg::outline::<HexPoint>(&flower).matches('Z').count(), 2);
+    }
+
+    #[test]
+    fn sheet() {
+        let mut domino = SimplePolyomino::new(vec![SimplePoint::new(0, 0), SimplePoint::new(1, 0)]);
+        domino.set_name(Some('D'));
+
+        let mut b = Board::from_string("XXX\nX\n\nJan - Feb\nMar\n").unwrap();
+        b.add_polyomino(&domino, &SimplePoint::new(0, 0));
+
+        let picture = svg::to_svg(&b);
+        assert!(picture.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="80""#));
+        assert_eq!(picture.matches("<path").count(), 1);
+        assert_eq!(picture.matches("<rect").count(), 2);
+        assert!(picture.contains(">Feb</text>"));
+        assert!(picture.contains(r#"<path d="M0 0 L40 0 L40 20 L0 20 Z""#));
+
+        // Three boards in rows of two
+        let boards = vec![b.clone(), b.clone(), b];
+        let picture = svg::sheet(&boards, 2);
+        assert!(picture.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="180" height="140""#));
+        assert_eq!(picture.matches("<g ").count(), 3);
+    }
+}
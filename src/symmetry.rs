@@ -0,0 +1,644 @@
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+
+use crate::board::board_utils;
+use crate::board::Board;
+use crate::board::BoardState;
+use crate::point::Lattice;
+use crate::point::Point;
+use crate::polyomino::Polyomino;
+
+// One of the symmetries of a lattice: an optional reflection in the y axis
+// followed by one of the rotations of the lattice (see `Point::turn`). The
+// square grid has eight, the triangular and hexagonal lattices twelve and
+// the cubic lattice 48.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Symmetry {
+    reflected: bool,
+    rotation: u8,
+}
+
+impl Symmetry {
+    pub const IDENTITY: Symmetry = Symmetry { reflected: false, rotation: 0 };
+
+    pub fn all(lattice: Lattice) -> Vec<Symmetry> {
+        let mut res = Vec::with_capacity(2 * lattice.rotations());
+
+        for reflected in [false, true] {
+            for rotation in 0..lattice.rotations() as u8 {
+                res.push(Symmetry { reflected, rotation });
+            }
+        }
+
+        res
+    }
+
+    // The cell at (x, y, z) of the lattice of `T` moved by the symmetry
+    pub fn apply<T: Point>(&self, x: i16, y: i16, z: i16) -> (i16, i16, i16) {
+        let p = if self.reflected { T::new_3d(x, y, z).flip() } else { T::new_3d(x, y, z) };
+        let p = p.turn(self.rotation as usize);
+
+        (p.x(), p.y(), p.z())
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Symmetry::IDENTITY
+    }
+}
+
+// Transforms the cells of the lattice of `L` and translates the result so that
+// the smallest x, y and z are all 0, or as near as the lattice allows. The
+// result is sorted, so two sets of cells are the same up to translation if
+// and only if their normalized forms are equal.
+fn normalize<L: Point, T: Copy + Ord>(pts: impl Iterator<Item = (i16, i16, i16, T)>, sym: Symmetry) -> Vec<(i16, i16, i16, T)> {
+    let mut res: Vec<(i16, i16, i16, T)> = pts
+        .map(|(x, y, z, t)| { let (x, y, z) = sym.apply::<L>(x, y, z); (x, y, z, t) })
+        .collect();
+
+    let min_x = res.iter().map(|p| p.0).min().unwrap_or(0);
+    let min_y = res.iter().map(|p| p.1).min().unwrap_or(0);
+    let min_z = res.iter().map(|p| p.2).min().unwrap_or(0);
+    let (min_x, min_y) = L::origin_offset(min_x, min_y);
+
+    for p in res.iter_mut() {
+        p.0 -= min_x;
+        p.1 -= min_y;
+        p.2 -= min_z;
+    }
+
+    res.sort();
+    res
+}
+
+type Shape = Vec<(i16, i16, i16, ())>;
+
+fn shape<P: Polyomino>(p: &P, sym: Symmetry) -> Shape {
+    normalize::<P::Pt, ()>(p.iter().map(|pt| (pt.x(), pt.y(), pt.z(), ())), sym)
+}
+
+// The shapes of the variations of a piece under the symmetry, sorted
+fn shapes<P: Polyomino>(variations: &[P], sym: Symmetry) -> Vec<Shape> {
+    let mut res: Vec<Shape> = variations.iter().map(|v| shape(v, sym)).collect();
+    res.sort();
+    res
+}
+
+// The symmetries that map the board onto itself. Every cell of the board
+// has to land on a cell of the same kind: empty, reserved, or filled by the
+// same pre-placed piece, and with the same label. The identity is always
+// included. A reflection of a solid board is included too, though it is
+// only a symmetry of a puzzle whose pieces have mirror images among them
+// (see `puzzle_symmetries`).
+pub fn board_symmetries<P: Polyomino>(b: &Board<P>) -> Vec<Symmetry> {
+    let mut labels: Vec<&str> = b.points().filter_map(|pt| b.label_3d(pt.x, pt.y, pt.z)).collect();
+    labels.sort();
+    labels.dedup();
+
+    let cells = || {
+        b.points().filter_map(|pt| {
+            let kind = match b.get_3d(pt.x, pt.y, pt.z) {
+                BoardState::Void => return None,
+                BoardState::Empty => (0, ' '),
+                BoardState::Reserved => (1, ' '),
+                BoardState::Fixed(c) => (2, c),
+                BoardState::Full(..) => (3, ' '),
+            };
+            let label = b.label_3d(pt.x, pt.y, pt.z).map_or(0, |l| labels.binary_search(&l).unwrap() + 1);
+
+            Some((pt.x, pt.y, pt.z, (kind, label)))
+        })
+    };
+
+    let original = normalize::<P::Pt, _>(cells(), Symmetry::IDENTITY);
+
+    Symmetry::all(P::Pt::LATTICE)
+        .into_iter()
+        .filter(|&sym| normalize::<P::Pt, _>(cells(), sym) == original)
+        .collect()
+}
+
+// The symmetries of the board that also map the set of pieces onto itself,
+// each with the piece that every piece becomes (see `piece_map`). A
+// reflection only maps a solution onto another one if every piece's mirror
+// image is one of the pieces, such as the two mirror image pieces of the
+// Soma cube.
+pub fn puzzle_symmetries<P: Polyomino>(b: &Board<P>, candidates: &[Vec<P>]) -> Vec<(Symmetry, Vec<usize>)> {
+    let ids = piece_ids(candidates);
+
+    board_symmetries(b)
+        .into_iter()
+        .filter_map(|sym| piece_map(candidates, &ids, sym).map(|map| (sym, map)))
+        .collect()
+}
+
+// For each candidate, the first candidate with the same variations. Pieces
+// with the same index are copies of each other, so solutions that only
+// swap them around are the same solution.
+fn piece_ids<P: Polyomino>(candidates: &[Vec<P>]) -> Vec<usize> {
+    let shapes: Vec<Vec<Shape>> = candidates.iter().map(|variations| shapes(variations, Symmetry::IDENTITY)).collect();
+
+    (0..candidates.len()).map(|i| shapes.iter().position(|s| *s == shapes[i]).unwrap()).collect()
+}
+
+// For each candidate, the id (see piece_ids) of the piece it turns into under
+// the symmetry, or None if some piece turns into something that isn't one of
+// the pieces, or into a piece with a different number of copies
+fn piece_map<P: Polyomino>(candidates: &[Vec<P>], ids: &[usize], sym: Symmetry) -> Option<Vec<usize>> {
+    let originals: Vec<Vec<Shape>> = candidates.iter().map(|variations| shapes(variations, Symmetry::IDENTITY)).collect();
+    let copies = |id: usize| ids.iter().filter(|&&i| i == id).count();
+
+    candidates.iter()
+        .enumerate()
+        .map(|(i, variations)| {
+            let image = shapes(variations, sym);
+            let id = ids[originals.iter().position(|s| *s == image)?];
+            (copies(id) == copies(ids[i])).then_some(id)
+        })
+        .collect()
+}
+
+// Part of a puzzle after breaking the board's symmetry: for each candidate,
+// the indexes of the variations that are tried. Solutions that are mapped
+// onto each other by one of the `residual` symmetries are the same solution,
+// so they still have to be weeded out. If the only residual symmetry is the
+// identity, every solution found is distinct, unless some of the pieces are
+// copies of each other.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymmetryCase {
+    pub variations: Vec<Vec<usize>>,
+    pub residual: Vec<Symmetry>,
+}
+
+impl SymmetryCase {
+    // The whole puzzle, with every variation, and no symmetry to break
+    pub fn all<P: Polyomino>(candidates: &[Vec<P>]) -> SymmetryCase {
+        SymmetryCase {
+            variations: candidates.iter().map(|variations| (0..variations.len()).collect()).collect(),
+            residual: vec![Symmetry::IDENTITY],
+        }
+    }
+}
+
+// Splits the puzzle of tiling `b` with `candidates`, each used once, into
+// cases that, between them, contain exactly one solution from every class
+// of solutions that are rotations or reflections of each other on this
+// board. `Solver::set_distinct` solves the cases one after the other.
+//
+// The symmetry is broken by fixing the orientation of a piece to one
+// representative from each class of orientations. Where the chosen piece is
+// itself partly symmetric, the symmetries that leave it unchanged are broken
+// with another piece, and so on. Any symmetry that is left once no piece
+// can break it is recorded in the case's `residual`.
+//
+// A piece that has a copy among the candidates can't break the symmetry, as
+// a rotated solution may have swapped the copies around, so it is left
+// alone. So are pieces that are already on the board, and pieces whose
+// variations don't include every rotation and reflection of the board.
+pub fn reduce_by_symmetry<P: Polyomino>(b: &Board<P>, candidates: &[Vec<P>]) -> Vec<SymmetryCase> {
+    reduce_with(b, candidates, &vec![true; candidates.len()])
+}
+
+// As reduce_by_symmetry, for candidates of which only those marked `once`
+// are sure to be used exactly once. The others can't break the symmetry.
+pub(crate) fn reduce_with<P: Polyomino>(b: &Board<P>, candidates: &[Vec<P>], once: &[bool]) -> Vec<SymmetryCase> {
+    let ids = piece_ids(candidates);
+    let mut fixed: Vec<bool> = (0..candidates.len())
+        .map(|i| !once[i] || ids.iter().filter(|&&id| id == ids[i]).count() > 1)
+        .collect();
+
+    // Pre-placed pieces are never moved, so they can't break the symmetry
+    for i in board_utils::fixed_candidates(b, candidates) {
+        fixed[i] = true;
+    }
+
+    let group = puzzle_symmetries(b, candidates).into_iter().map(|(sym, _)| sym).collect();
+    let mut cases = Vec::new();
+    reduce(candidates, SymmetryCase::all(candidates).variations, group, &mut fixed, &mut cases);
+
+    cases
+}
+
+// A representative orientation (an index into a piece's variations) and the
+// symmetries that leave it unchanged
+type OrientationClass = (usize, Vec<Symmetry>);
+
+fn reduce<P: Polyomino>(candidates: &[Vec<P>], variations: Vec<Vec<usize>>, group: Vec<Symmetry>,
+                        fixed: &mut [bool], cases: &mut Vec<SymmetryCase>) {
+    if group.len() == 1 {
+        cases.push(SymmetryCase { variations, residual: group });
+        return;
+    }
+
+    // For every piece, split its orientations into classes that are mapped onto
+    // each other by the group. One orientation from each class is kept, along with
+    // the symmetries that leave it unchanged. The best piece to fix is the one
+    // whose orientations are left unchanged by the fewest symmetries.
+    let mut best: Option<(usize, usize, Vec<OrientationClass>)> = None;
+
+    'pieces: for (i, indexes) in variations.iter().enumerate() {
+        if fixed[i] {
+            continue;
+        }
+
+        let shapes: Vec<_> = indexes.iter().map(|&v| shape(&candidates[i][v], Symmetry::IDENTITY)).collect();
+        let mut seen = vec![false; indexes.len()];
+        let mut classes = Vec::new();
+
+        for n in 0..indexes.len() {
+            if seen[n] {
+                continue;
+            }
+
+            let mut stabilizer = Vec::new();
+            for &sym in &group {
+                let image = shape(&candidates[i][indexes[n]], sym);
+                if image == shapes[n] {
+                    stabilizer.push(sym);
+                }
+
+                // The piece can only be fixed if every image is a variation, so
+                // it can't be one that the symmetry turns into another piece
+                match shapes.iter().position(|s| *s == image) {
+                    Some(m) => seen[m] = true,
+                    None => continue 'pieces,
+                }
+            }
+
+            classes.push((indexes[n], stabilizer));
+        }
+
+        let largest = classes.iter().map(|c| c.1.len()).max().unwrap_or(group.len());
+        if largest < group.len() && best.as_ref().is_none_or(|b| largest < b.1) {
+            best = Some((i, largest, classes));
+        }
+    }
+
+    let Some((piece, _, classes)) = best else {
+        cases.push(SymmetryCase { variations, residual: group });
+        return;
+    };
+
+    // Orientations that are left unchanged by the same symmetries can share a case
+    let mut by_stabilizer: Vec<(Vec<Symmetry>, Vec<usize>)> = Vec::new();
+    for (v, stabilizer) in classes {
+        match by_stabilizer.iter_mut().find(|(s, _)| *s == stabilizer) {
+            Some((_, indexes)) => indexes.push(v),
+            None => by_stabilizer.push((stabilizer, vec![v])),
+        }
+    }
+
+    fixed[piece] = true;
+    for (stabilizer, indexes) in by_stabilizer {
+        let mut case_variations = variations.clone();
+        case_variations[piece] = indexes;
+
+        reduce(candidates, case_variations, stabilizer, fixed, cases);
+    }
+    fixed[piece] = false;
+}
+
+// Canonical encodings of the solutions of a board. The key of a solution is
+// the same as that of another if and only if one is mapped onto the other by
+// one of the symmetries, so solutions can be told apart up to symmetry by
+// comparing keys. Pieces that are copies of each other count as the same
+// piece.
+#[derive(Clone)]
+pub struct SolutionKeys {
+    // The empty cells of the board before it was solved
+    cells: Vec<(i16, i16, i16)>,
+    // Each symmetry with the piece each piece turns into
+    symmetries: Vec<(Symmetry, Vec<usize>)>,
+    // The piece each variation belongs to, by its address
+    pieces: FxHashMap<usize, usize>,
+    copies: bool,
+}
+
+// A canonical encoding of a solution: its empty cells, each with the index
+// of the first candidate of the piece covering it and the index in the key
+// of that piece's first cell, or `usize::MAX` twice if it is left uncovered.
+// The second index tells apart copies of a piece that touch.
+pub type SolutionKey = Vec<(i16, i16, i16, usize, usize)>;
+
+impl SolutionKeys {
+    // Keys for the solutions of `b`, up to the symmetries of the puzzle
+    pub fn new<P: Polyomino>(b: &Board<P>, candidates: &[Vec<P>]) -> SolutionKeys {
+        SolutionKeys::with_symmetries(b, candidates, &board_symmetries(b))
+    }
+
+    // Keys for the solutions of `b`, up to some of its symmetries. Those that
+    // don't map the pieces onto themselves are left out.
+    pub fn with_symmetries<P: Polyomino>(b: &Board<P>, candidates: &[Vec<P>], symmetries: &[Symmetry]) -> SolutionKeys {
+        let ids = piece_ids(candidates);
+        let mut pieces = FxHashMap::default();
+        for (i, variations) in candidates.iter().enumerate() {
+            for v in variations {
+                pieces.insert(v as *const P as usize, ids[i]);
+            }
+        }
+
+        SolutionKeys {
+            cells: board_utils::get_all_unoccupied(b).iter().map(|pt| (pt.x, pt.y, pt.z)).collect(),
+            symmetries: symmetries.iter().filter_map(|&sym| piece_map(candidates, &ids, sym).map(|map| (sym, map))).collect(),
+            pieces,
+            copies: ids.iter().enumerate().any(|(i, &id)| id != i),
+        }
+    }
+
+    // True if some of the candidates are copies of each other, so that
+    // different solutions can have the same key even without symmetries.
+    pub fn has_copies(&self) -> bool {
+        self.copies
+    }
+
+    // The empty cells with the piece covering each one, and a number for the
+    // placement it belongs to
+    fn cells<P: Polyomino>(&self, solution: &Board<P>) -> Vec<(i16, i16, i16, (usize, usize))> {
+        let mut placements = FxHashMap::default();
+
+        self.cells.iter()
+            .map(|&(x, y, z)| match solution.get_3d(x, y, z) {
+                BoardState::Full(p, _, ax, ay, az) => {
+                    let next = placements.len();
+                    let placement = *placements.entry((p as *const P as usize, ax, ay, az)).or_insert(next);
+                    (x, y, z, (self.pieces[&(p as *const P as usize)], placement))
+                }
+                _ => (x, y, z, (usize::MAX, usize::MAX)),
+            })
+            .collect()
+    }
+
+    // The cells under a symmetry, with each piece turned into the one the
+    // symmetry makes it and each placement numbered by where its first cell
+    // ends up
+    fn image<L: Point>(cells: &[(i16, i16, i16, (usize, usize))], (sym, map): &(Symmetry, Vec<usize>)) -> SolutionKey {
+        let mut first = FxHashMap::default();
+        let cells = cells.iter().map(|&(x, y, z, (id, placement))| match id {
+            usize::MAX => (x, y, z, (id, placement)),
+            _ => (x, y, z, (map[id], placement)),
+        });
+
+        normalize::<L, (usize, usize)>(cells, *sym)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (x, y, z, (id, placement)))| match id {
+                usize::MAX => (x, y, z, id, usize::MAX),
+                _ => (x, y, z, id, *first.entry(placement).or_insert(i)),
+            })
+            .collect()
+    }
+
+    // The key of a solution, which must use the candidates these keys were
+    // made for.
+    pub fn key<P: Polyomino>(&self, solution: &Board<P>) -> SolutionKey {
+        let cells = self.cells(solution);
+
+        self.symmetries
+            .iter()
+            .map(|sym| SolutionKeys::image::<P::Pt>(&cells, sym))
+            .min()
+            .unwrap_or_default()
+    }
+
+    // The number of different solutions that the symmetries turn a solution
+    // into, counting the solution itself.
+    pub fn orbit_size<P: Polyomino>(&self, solution: &Board<P>) -> usize {
+        let cells = self.cells(solution);
+
+        self.symmetries
+            .iter()
+            .map(|sym| SolutionKeys::image::<P::Pt>(&cells, sym))
+            .collect::<FxHashSet<_>>()
+            .len()
+    }
+}
+
+// The solutions of `b` without those that are a rotation or reflection of
+// one that comes before them, under the symmetries of the puzzle.
+pub fn distinct_solutions<'a, P: Polyomino>(b: &Board<'a, P>, candidates: &[Vec<P>],
+                                            solutions: impl IntoIterator<Item = Board<'a, P>>) -> Vec<Board<'a, P>> {
+    let keys = SolutionKeys::new(b, candidates);
+    let mut seen = FxHashSet::default();
+
+    solutions.into_iter().filter(|s| seen.insert(keys.key(s))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::point::CubePoint;
+    use crate::point::HexPoint;
+    use crate::point::Point;
+    use crate::point::SimplePoint;
+    use crate::point::TrianglePoint;
+    use crate::polyomino::Polyomino;
+    use crate::polyomino::SimplePolyomino;
+    use crate::solver::Multiplicity;
+    use crate::solver::Solver;
+    use crate::symmetry;
+    use crate::symmetry::SymmetryCase;
+    use crate::utils;
+    use crate::utils::Orientations;
+    use crate::utils::PredefinedPolyominoes;
+    use crate::utils::Restrictions;
+
+    type Poly = SimplePolyomino<SimplePoint>;
+
+    fn build(pts: &[(i16, i16)]) -> Poly {
+        SimplePolyomino::new(pts.iter().map(|&(x, y)| SimplePoint::new(x, y)).collect())
+    }
+
+    // The number of distinct solutions and the number counting every
+    // rotation and reflection
+    fn count(b: &Board<Poly>, candidates: &[Vec<Poly>]) -> (u32, u32) {
+        let mut board = b.clone();
+        let mut solver = Solver::new(&mut board, candidates);
+        solver.set_distinct(true);
+
+        (solver.count_solutions(), solver.raw_count())
+    }
+
+    fn pentominoes() -> Vec<Vec<Poly>> {
+        utils::build_variations(&utils::get_polyominoes(PredefinedPolyominoes::Pentominoes).unwrap(), Restrictions::None)
+    }
+
+    #[test]
+    fn board_symmetries() {
+        let group_size = |name: &str| {
+            let b = Board::<Poly>::from_file(name).unwrap();
+            symmetry::board_symmetries(&b).len()
+        };
+
+        assert_eq!(symmetry::board_symmetries(&Board::<Poly>::new(10, 6)).len(), 4);
+        assert_eq!(symmetry::board_symmetries(&Board::<Poly>::new(8, 8)).len(), 8);
+        assert_eq!(group_size("data/b8x8holes.board"), 8);
+        assert_eq!(group_size("data/b8x8nocorners.board"), 8);
+        assert_eq!(group_size("data/cross.board"), 4);
+        assert_eq!(group_size("data/knight.board"), 1);
+
+        // Reserved cells, pre-placed pieces and labels all have to be mapped
+        // onto their own kind
+        let squares = |rows: &str| symmetry::board_symmetries(&Board::<Poly>::from_string(rows).unwrap()).len();
+        assert_eq!(squares("XXX\nX.X\nXXX\n"), 8);
+        assert_eq!(squares(".XX\nXXX\nXXX\n"), 2);
+        assert_eq!(squares("aXX\nXXX\nXXX\n"), 2);
+        assert_eq!(squares("aXX\nXXX\nXbX\n"), 1);
+        assert_eq!(squares("XXX\nXXX\nXXX\n\n1 2 1\n2 3 2\n1 2 1\n"), 8);
+        assert_eq!(squares("XXX\nXXX\nXXX\n\n1 2 3\n2 3 2\n1 2 1\n"), 2);
+
+        // Triangles can be turned a sixth of a turn at a time
+        let triangles = |rows: &str| {
+            let b = Board::<SimplePolyomino<TrianglePoint>>::from_string(rows).unwrap();
+            symmetry::board_symmetries(&b).len()
+        };
+        assert_eq!(triangles("  X\n XXX\nXXXXX\n"), 6);
+        assert_eq!(triangles("  XXXXX\n XXXXXXX\n XXXXXXX\n  XXXXX\n"), 12);
+        assert_eq!(triangles(&std::fs::read_to_string("data/hexiamonds-rhombus.board").unwrap()), 4);
+        assert_eq!(triangles("XXXX\n"), 2);
+
+        // So can hexagons
+        let hexagons = |rows: &str| {
+            let b = Board::<SimplePolyomino<HexPoint>>::from_string(rows).unwrap();
+            symmetry::board_symmetries(&b).len()
+        };
+        assert_eq!(hexagons(" XX\nXXX\nXX\n"), 12);
+        assert_eq!(hexagons("XX\nXX\n"), 4);
+        assert_eq!(hexagons("XXX\nXXX\n"), 2);
+    }
+
+    #[test]
+    fn solid_symmetries() {
+        type Cube = SimplePolyomino<CubePoint>;
+
+        // A cube has 24 rotations and as many reflections, which are only
+        // symmetries of the puzzle if each piece has its mirror image
+        let b = Board::<Cube>::new_box(3, 3, 3);
+        assert_eq!(symmetry::board_symmetries(&b).len(), 48);
+        assert_eq!(symmetry::board_symmetries(&Board::<Cube>::new_box(3, 2, 4)).len(), 8);
+
+        let screws: Vec<Cube> = utils::read_polyomino_string("A\n-\nAA\n A\n\nB\n-\nB\nBB\n").unwrap();
+        let both = utils::build_orientations(&screws, Orientations::Rotations);
+        let one = utils::build_orientations(&screws[..1], Orientations::Rotations);
+        assert_eq!(symmetry::puzzle_symmetries(&b, &both).len(), 48);
+        assert_eq!(symmetry::puzzle_symmetries(&b, &one).len(), 24);
+
+        // A reflection swaps the two screws round
+        let (_, map) = symmetry::puzzle_symmetries(&b, &both).into_iter().find(|(sym, _)| sym.reflected).unwrap();
+        assert_eq!(map, vec![1, 0]);
+    }
+
+    #[test]
+    fn rectangle() {
+        assert_eq!(count(&Board::new(20, 3), &pentominoes()), (2, 8));
+    }
+
+    #[test]
+    fn board_with_holes() {
+        let b = Board::from_file("data/b8x8holes.board").unwrap();
+
+        assert_eq!(count(&b, &pentominoes()), (21, 21 * 8));
+    }
+
+    #[test]
+    fn symmetric_pieces() {
+        // Every orientation of each piece is left unchanged by some symmetry of
+        // the square, so the symmetry can't be broken by a single piece
+        let polys = vec![
+            build(&[(0, 0), (0, 1), (1, 0)]),
+            build(&[(0, 0), (0, 1), (0, 2)]),
+            build(&[(0, 0), (0, 1)]),
+            build(&[(0, 0)]),
+        ];
+        let candidates = utils::build_variations(&polys, Restrictions::None);
+
+        let cases = symmetry::reduce_by_symmetry(&Board::new(3, 3), &candidates);
+        assert!(cases.iter().all(|c| c.variations[0].len() == 1));
+
+        let mut b = Board::new(3, 3);
+        let all = Solver::new(&mut b, &candidates).count_solutions();
+        assert_eq!(count(&Board::new(3, 3), &candidates), (6, all));
+    }
+
+    #[test]
+    fn fully_symmetric_pieces() {
+        // Nothing can break the symmetry, so the solutions have to be compared
+        let polys = vec![
+            build(&[(0, 0), (0, 1)]),
+            build(&[(0, 0), (0, 1), (1, 0), (1, 1)]),
+        ];
+        let candidates = utils::build_variations(&polys, Restrictions::None);
+
+        assert_eq!(symmetry::reduce_by_symmetry(&Board::new(3, 2), &candidates).len(), 1);
+        assert_eq!(count(&Board::new(3, 2), &candidates), (1, 2));
+    }
+
+    #[test]
+    fn copies() {
+        // Two dominoes fill a square in two ways, which are the same up to
+        // symmetry, and swapping the dominoes around makes no difference
+        let domino = build(&[(0, 0), (0, 1)]);
+        let candidates = utils::build_variations(&[domino.clone(), domino.clone()], Restrictions::None);
+        let mut b = Board::new(2, 2);
+        assert_eq!(Solver::new(&mut b, &candidates).count_solutions(), 4);
+        assert_eq!(count(&Board::new(2, 2), &candidates), (1, 2));
+
+        // A copy can't break the symmetry, as it may be either one
+        let l = build(&[(0, 0), (0, 1), (0, 2), (1, 0)]);
+        let candidates = utils::build_variations(&[l.clone(), l, domino.clone()], Restrictions::None);
+        let b = Board::new(2, 5);
+        assert!(symmetry::reduce_by_symmetry(&b, &candidates).iter().all(|c| c.variations[0].len() == 8));
+        let mut board = b.clone();
+        let keys = symmetry::SolutionKeys::new(&b, &candidates);
+        let solutions = Solver::new(&mut board, &candidates).solve().clone();
+        let expected = symmetry::distinct_solutions(&b, &candidates, solutions).len() as u32;
+        assert_eq!(count(&b, &candidates).0, expected);
+        assert!(keys.has_copies());
+
+        // Nor can a piece used more than once
+        let candidates = utils::build_variations(&[domino], Restrictions::None);
+        let mut board = Board::new(2, 2);
+        let mut solver = Solver::new(&mut board, &candidates);
+        solver.set_multiplicity(0, Multiplicity::exactly(2));
+        solver.set_distinct(true);
+        assert_eq!(solver.count_solutions(), 1);
+        assert_eq!(solver.raw_count(), 2);
+    }
+
+    #[test]
+    fn distinct_solutions() {
+        let candidates = pentominoes();
+        let b = Board::new(20, 3);
+
+        let mut board = b.clone();
+        let mut solver = Solver::new(&mut board, &candidates);
+        let solutions = solver.solve().clone();
+        assert_eq!(solutions.len(), 8);
+        assert_eq!(symmetry::distinct_solutions(&b, &candidates, solutions.iter().cloned()).len(), 2);
+
+        // Each solution is one of four that are the same up to symmetry
+        let keys = symmetry::SolutionKeys::new(&b, &candidates);
+        let first = keys.key(&solutions[0]);
+        assert_eq!(solutions.iter().filter(|s| keys.key(s) == first).count(), 4);
+        assert_eq!(keys.orbit_size(&solutions[0]), 4);
+    }
+
+    #[test]
+    fn fixed_piece() {
+        // The pre-placed L tromino hangs off the side of a 4x3 rectangle, so
+        // the board has no symmetry even though its empty cells do
+        let polys = vec![
+            build(&[(0, 0), (0, 1), (1, 0)]),
+            build(&[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]),
+            build(&[(0, 0), (0, 1), (0, 2), (1, 2)]),
+            build(&[(0, 0), (0, 1), (0, 2)]),
+        ];
+        let candidates = utils::build_variations(&polys, Restrictions::None);
+        let b = Board::from_string("aaXXXX\na XXXX\n  XXXX\n").unwrap();
+
+        assert_eq!(symmetry::board_symmetries(&b).len(), 1);
+        assert_eq!(symmetry::reduce_by_symmetry(&b, &candidates), vec![SymmetryCase::all(&candidates)]);
+
+        let mut board = b.clone();
+        let all = Solver::new(&mut board, &candidates).count_solutions();
+        assert_eq!(count(&b, &candidates), (all, all));
+    }
+}
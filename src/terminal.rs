@@ -0,0 +1,231 @@
+use crate::board::board_utils;
+use crate::board::Board;
+use crate::board::BoardState;
+use crate::point::Lattice;
+use crate::point::Point;
+use crate::polyomino::Polyomino;
+
+/// The background colors given to pieces, from the 256 color palette.
+/// Pieces that touch form a planar graph, which never needs more than six
+/// colors when they are handed out the way `color_pieces` does. Pieces in a
+/// solid board can touch many more, and two that touch may then get the same
+/// color.
+pub const PALETTE: [u8; 8] = [210, 150, 117, 222, 183, 116, 216, 252];
+
+/// The board drawn for a terminal, like its `Display` output but with
+/// Unicode box-drawing lines between the pieces and each piece on a colored
+/// background. Pieces that touch always get different colors. Boards of
+/// triangles, hexagons or cubes have no lines, just a character for each cell
+/// as in a board file.
+pub fn to_ansi<P: Polyomino>(b: &Board<P>) -> String {
+    let numbers = board_utils::number_pieces(b);
+    let colors = color_pieces(b, &numbers);
+
+    if P::Pt::LATTICE != Lattice::Square {
+        return cells(b, &numbers, &colors);
+    }
+
+    // What a cell belongs to, for deciding where the lines go
+    let region = |x: i16, y: i16| -> (u8, usize) {
+        match b.get(x, y) {
+            BoardState::Void => (0, 0),
+            BoardState::Empty => (1, 0),
+            BoardState::Reserved => (2, 0),
+            BoardState::Full(..) | BoardState::Fixed(_) => (3, numbers[b.to_idx(x, y, 0)].unwrap()),
+        }
+    };
+    let background = |x: i16, y: i16| match region(x, y) {
+        (3, n) => Some(PALETTE[colors[n] % PALETTE.len()]),
+        _ => None,
+    };
+    let line = |a: (i16, i16), b: (i16, i16)| region(a.0, a.1) != region(b.0, b.1);
+
+    let mut res = String::new();
+    let mut current = None;
+
+    for gy in 0..=2 * b.get_height() {
+        for gx in 0..=2 * b.get_width() {
+            // The cell below and to the right of this point of the grid
+            let (x, y) = (gx / 2, gy / 2);
+
+            let (text, color) = match (gx % 2, gy % 2) {
+                (1, 1) => (b.get(x, y).rep(), background(x, y)),
+                (0, 1) if line((x - 1, y), (x, y)) => ("│".to_string(), None),
+                (0, 1) => (" ".to_string(), background(x, y)),
+                (1, 0) if line((x, y - 1), (x, y)) => ("─".to_string(), None),
+                (1, 0) => (" ".to_string(), background(x, y)),
+                _ => {
+                    let up = line((x - 1, y - 1), (x, y - 1));
+                    let down = line((x - 1, y), (x, y));
+                    let left = line((x - 1, y - 1), (x - 1, y));
+                    let right = line((x, y - 1), (x, y));
+
+                    match corner(up, down, left, right) {
+                        ' ' => (" ".to_string(), background(x, y)),
+                        c => (c.to_string(), None),
+                    }
+                }
+            };
+
+            if color != current {
+                res.push_str(&match color {
+                    Some(c) => format!("\x1b[30;48;5;{}m", c),
+                    None => "\x1b[0m".to_string(),
+                });
+                current = color;
+            }
+            res.push_str(&text);
+        }
+
+        if current.take().is_some() {
+            res.push_str("\x1b[0m");
+        }
+        res.push('\n');
+    }
+
+    res
+}
+
+fn cells<P: Polyomino>(b: &Board<P>, numbers: &[Option<usize>], colors: &[usize]) -> String {
+    let mut res = String::new();
+
+    for z in 0..b.get_depth() {
+        if z > 0 {
+            res.push_str("-\n");
+        }
+
+        for y in b.row_range() {
+            let mut row = String::new();
+            for x in b.col_range() {
+                match numbers[b.to_idx(x, y, z)] {
+                    Some(n) => row.push_str(&format!("\x1b[30;48;5;{}m{}\x1b[0m", PALETTE[colors[n] % PALETTE.len()], b.get_3d(x, y, z).rep())),
+                    None => row.push_str(&b.get_3d(x, y, z).rep()),
+                }
+            }
+
+            res.push_str(row.trim_end());
+            res.push('\n');
+        }
+    }
+
+    res
+}
+
+// The box-drawing character joining the lines that meet at a point
+fn corner(up: bool, down: bool, left: bool, right: bool) -> char {
+    match (up, down, left, right) {
+        (false, false, false, false) => ' ',
+        (true, false, false, false) => '╵',
+        (false, true, false, false) => '╷',
+        (false, false, true, false) => '╴',
+        (false, false, false, true) => '╶',
+        (true, true, false, false) => '│',
+        (false, false, true, true) => '─',
+        (false, true, false, true) => '┌',
+        (false, true, true, false) => '┐',
+        (true, false, false, true) => '└',
+        (true, false, true, false) => '┘',
+        (true, true, false, true) => '├',
+        (true, true, true, false) => '┤',
+        (false, true, true, true) => '┬',
+        (true, false, true, true) => '┴',
+        (true, true, true, true) => '┼',
+    }
+}
+
+// An index into PALETTE for each piece, different for pieces that touch.
+// The pieces are colored greedily, each after all the pieces that were left
+// with the fewest neighbours when it was taken out. In a planar graph there
+// is always a piece with at most five neighbours left, so no piece has more
+// than five neighbours colored before it. The drawings still wrap the
+// indexes round PALETTE, so that pieces in a box, which can touch many more,
+// share colors rather than run out of them.
+fn color_pieces<P: Polyomino>(b: &Board<P>, numbers: &[Option<usize>]) -> Vec<usize> {
+    let count = numbers.iter().flatten().max().map_or(0, |n| n + 1);
+    let mut neighbours = vec![Vec::new(); count];
+
+    let at = |x: i16, y: i16, z: i16| numbers[b.to_idx(x, y, z)];
+    for pt in b.points() {
+        let (x, y, z) = (pt.x, pt.y, pt.z);
+        let Some(n) = at(x, y, z) else { continue };
+        let adjacent = P::Pt::neighbours(x, y).iter()
+            .map(|&(dx, dy, dz)| (x + dx, y + dy, z + dz))
+            .filter(|&(ax, ay, az)| b.col_range().contains(&ax) && b.row_range().contains(&ay) && (0..b.get_depth()).contains(&az))
+            .filter_map(|(ax, ay, az)| at(ax, ay, az));
+
+        for m in adjacent {
+            if m != n && !neighbours[n].contains(&m) {
+                neighbours[n].push(m);
+                neighbours[m].push(n);
+            }
+        }
+    }
+
+    let mut left: Vec<usize> = (0..count).collect();
+    let mut order = Vec::new();
+    while !left.is_empty() {
+        let degree = |n: usize| neighbours[n].iter().filter(|m| left.contains(m)).count();
+        let i = (0..left.len()).min_by_key(|&i| degree(left[i])).unwrap();
+        order.push(left.swap_remove(i));
+    }
+
+    let mut colors = vec![usize::MAX; count];
+    for &n in order.iter().rev() {
+        colors[n] = (0..).find(|c| neighbours[n].iter().all(|&m| colors[m] != *c)).unwrap();
+    }
+
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::board_utils;
+    use crate::board::Board;
+    use crate::point::SimplePoint;
+    use crate::polyomino::SimplePolyomino;
+    use crate::solver::Solver;
+    use crate::terminal;
+    use crate::utils;
+    use crate::utils::PredefinedPolyominoes;
+    use crate::utils::Restrictions;
+
+    #[test]
+    fn boxes() {
+        let b: Board<SimplePolyomino<SimplePoint>> = Board::from_string("ab.\n b\n").unwrap();
+
+        let plain = concat!("┌─┬─┬─┐\n",
+                            "│a│b│.│\n",
+                            "└─┤ ├─┘\n",
+                            "  │b│  \n",
+                            "  └─┘  \n");
+        let drawn = terminal::to_ansi(&b).replace("\x1b[0m", "").replace("\x1b[30;48;5;210m", "").replace("\x1b[30;48;5;150m", "");
+        assert_eq!(drawn, plain);
+        assert!(terminal::to_ansi(&b).starts_with("┌─┬─┬─┐\n│\x1b[30;48;5;150ma\x1b[0m│\x1b[30;48;5;210mb\x1b[0m│.│\n"));
+    }
+
+    #[test]
+    fn touching_pieces() {
+        let pentominoes: Vec<SimplePolyomino<SimplePoint>> = utils::get_polyominoes(PredefinedPolyominoes::Pentominoes).unwrap();
+        let polys = utils::build_variations(&pentominoes, Restrictions::None);
+        let mut b = Board::new(10, 6);
+        let mut solver = Solver::new(&mut b, &polys);
+
+        for s in solver.solutions().take(20) {
+            let numbers = board_utils::number_pieces(&s);
+            let colors = terminal::color_pieces(&s, &numbers);
+            assert_eq!(colors.len(), 12);
+
+            let at = |x: i16, y: i16| numbers[s.to_idx(x, y, 0)].unwrap();
+            for x in 0..10 {
+                for y in 0..6 {
+                    if x < 9 && at(x, y) != at(x + 1, y) {
+                        assert_ne!(colors[at(x, y)], colors[at(x + 1, y)]);
+                    }
+                    if y < 5 && at(x, y) != at(x, y + 1) {
+                        assert_ne!(colors[at(x, y)], colors[at(x, y + 1)]);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,18 +1,29 @@
 use std::fs;
 use std::hash::Hash;
-use std::io::Error;
-
-use lazy_static::lazy_static;
+use std::iter;
+use std::str::FromStr;
 
 use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
 
+use crate::enumeration;
+use crate::enumeration::Holes;
+use crate::enumeration::Kind;
+use crate::error::ParseError;
+use crate::point::Lattice;
 use crate::point::Point;
 use crate::polyomino::Polyomino;
 
+// Limits the orientations of the first piece with 8 variations. This is only
+// correct for plain rectangles and squares, and the counts of the solver then
+// leave out the solutions it skips. Solver::set_distinct works out the
+// symmetry of any board instead, so only None is left for new code.
 #[allow(dead_code)]
 pub enum Restrictions {
     None,
+    #[deprecated(note = "use Solver::set_distinct, which works on any board")]
     SquareSymmetry,
+    #[deprecated(note = "use Solver::set_distinct, which works on any board")]
     RectangularSymmetry, // SingleSided
 }
 
@@ -22,8 +33,9 @@ pub fn build_variations<P:Polyomino>(polys: &[P], restrict: Restrictions) -> Vec
     let mut found_asym = false;
 
     for p in polys {
-        let mut variations = p.make_all_variations();
+        let mut variations = p.make_orientations();
 
+        #[allow(deprecated)]
         match restrict {
             Restrictions::None => (),
             Restrictions::SquareSymmetry => {
@@ -45,72 +57,334 @@ pub fn build_variations<P:Polyomino>(polys: &[P], restrict: Restrictions) -> Vec
     res
 }
 
-#[derive(Eq,Hash,PartialEq)]
+// Which orientations of each piece the solver may use: only its rotations,
+// or its mirror images as well. Flat pieces can be turned over, so they have
+// all of them unless they are one-sided. Solid pieces can't be turned into
+// their mirror images, so they only have their 24 rotations unless mirror
+// images are allowed, which gives up to 48.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientations {
+    Rotations,
+    All,
+}
+
+impl Orientations {
+    // The orientations build_variations gives pieces on the lattice
+    pub fn default_for(lattice: Lattice) -> Orientations {
+        match lattice {
+            Lattice::Cubic => Orientations::Rotations,
+            _ => Orientations::All,
+        }
+    }
+}
+
+// The variations of each piece in the given orientations, in the same order
+// as Polyomino::make_all_variations
+pub fn build_orientations<P:Polyomino>(polys: &[P], orientations: Orientations) -> Vec<Vec<P>> {
+    polys.iter()
+        .map(|p| match orientations {
+            Orientations::Rotations => p.make_rotations(),
+            Orientations::All => p.make_all_variations(),
+        })
+        .collect()
+}
+
+// The free polyominoes of each size from 1 to 10. The same sets are the
+// polyiamonds or polyhexes of that size when read onto a triangular or
+// hexagonal lattice, so the variants are named by size.
+#[derive(Debug,Eq,Hash,PartialEq)]
 pub enum PredefinedPolyominoes {
-    Monominoes,
-    Dominoes,
-    Triominoes,
-    Tetrominoes,
-    Pentominoes,
-    Hexominoes,
-    Heptominoes,
-    Octominoes,
-}
-
-lazy_static! {
-    static ref HASHMAP: FxHashMap<PredefinedPolyominoes, &'static str> = {
-        let mut hm = FxHashMap::default();
-        hm.insert(PredefinedPolyominoes::Monominoes, include_str!("../data/monomino.poly"));
-        hm.insert(PredefinedPolyominoes::Dominoes, include_str!("../data/domino.poly"));
-        hm.insert(PredefinedPolyominoes::Triominoes, include_str!("../data/triomino.poly"));
-        hm.insert(PredefinedPolyominoes::Tetrominoes, include_str!("../data/tetromino.poly"));
-        hm.insert(PredefinedPolyominoes::Pentominoes, include_str!("../data/pentomino.poly"));
-        hm.insert(PredefinedPolyominoes::Hexominoes, include_str!("../data/hexomino.poly"));
-        hm.insert(PredefinedPolyominoes::Heptominoes, include_str!("../data/heptomino.poly"));
-        hm.insert(PredefinedPolyominoes::Octominoes, include_str!("../data/octomino.poly"));
-        hm
-    };
+    Size1,
+    Size2,
+    Size3,
+    Size4,
+    Size5,
+    Size6,
+    Size7,
+    Size8,
+    Size9,
+    Size10,
+}
+
+// The names the sets had before they were named by size, which are still
+// the right ones on a square lattice
+#[allow(non_upper_case_globals)]
+impl PredefinedPolyominoes {
+    pub const Monominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size1;
+    pub const Dominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size2;
+    pub const Triominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size3;
+    pub const Tetrominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size4;
+    pub const Pentominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size5;
+    pub const Hexominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size6;
+    pub const Heptominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size7;
+    pub const Octominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size8;
+    pub const Nonominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size9;
+    pub const Decominoes: PredefinedPolyominoes = PredefinedPolyominoes::Size10;
+}
+
+impl PredefinedPolyominoes {
+    // The number of cells in each piece of the set
+    pub fn size(&self) -> usize {
+        match self {
+            PredefinedPolyominoes::Size1 => 1,
+            PredefinedPolyominoes::Size2 => 2,
+            PredefinedPolyominoes::Size3 => 3,
+            PredefinedPolyominoes::Size4 => 4,
+            PredefinedPolyominoes::Size5 => 5,
+            PredefinedPolyominoes::Size6 => 6,
+            PredefinedPolyominoes::Size7 => 7,
+            PredefinedPolyominoes::Size8 => 8,
+            PredefinedPolyominoes::Size9 => 9,
+            PredefinedPolyominoes::Size10 => 10,
+        }
+    }
+
+    // The lattice of a set, going by its name: the polyiamonds, such as the
+    // hexiamonds, are made of triangles and the polyhexes, such as the
+    // tetrahexes, of hexagons
+    pub fn lattice(name: &str) -> Lattice {
+        let name = name.to_lowercase();
+
+        if name.contains("iamond") {
+            Lattice::Triangular
+        } else if name.trim_end_matches("es").ends_with("hex") {
+            Lattice::Hexagonal
+        } else {
+            Lattice::Square
+        }
+    }
 }
 
-pub fn get_polyominoes<P:Polyomino>(polytype: PredefinedPolyominoes) -> Result<Vec<P>, Error> {
-    read_polyomino_string(HASHMAP.get(&polytype).unwrap())
+// Accepts names such as "pentominoes" or "pentomino", and the names of the
+// polyiamonds of the same sizes up to "octiamonds" and of the polyhexes up to
+// "hexahexes"
+impl FromStr for PredefinedPolyominoes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim_end_matches("es").trim_end_matches('s') {
+            "monomino" | "moniamond" | "monohex" => Ok(PredefinedPolyominoes::Size1),
+            "domino" | "diamond" | "dihex" => Ok(PredefinedPolyominoes::Size2),
+            "triomino" | "triamond" | "trihex" => Ok(PredefinedPolyominoes::Size3),
+            "tetromino" | "tetriamond" | "tetrahex" => Ok(PredefinedPolyominoes::Size4),
+            "pentomino" | "pentiamond" | "pentahex" => Ok(PredefinedPolyominoes::Size5),
+            "hexomino" | "hexiamond" | "hexahex" => Ok(PredefinedPolyominoes::Size6),
+            "heptomino" | "heptiamond" => Ok(PredefinedPolyominoes::Size7),
+            "octomino" | "octiamond" => Ok(PredefinedPolyominoes::Size8),
+            "nonomino" => Ok(PredefinedPolyominoes::Size9),
+            "decomino" => Ok(PredefinedPolyominoes::Size10),
+            _ => Err(format!("unknown set of polyominoes '{}'", s)),
+        }
+    }
+}
+
+// Every free polyomino in the set, including those with holes, named by
+// name_pieces. The sets are generated rather than read, so this can't fail,
+// but it returns a Result like read_polyominoes_from_file.
+pub fn get_polyominoes<P:Polyomino>(polytype: PredefinedPolyominoes) -> Result<Vec<P>, ParseError> {
+    let mut res = enumeration::polyominoes(polytype.size(), Kind::Free, Holes::Include);
+    name_pieces(&mut res);
+    Ok(res)
 }
 
-pub fn read_polyominoes_from_file<P:Polyomino>(name: &str) -> Result<Vec<P>, Error> {
+pub fn read_polyominoes_from_file<P:Polyomino>(name: &str) -> Result<Vec<P>, ParseError> {
     let contents = fs::read_to_string(name)?;
     
     read_polyomino_string(&contents)
 }
 
-fn read_polyomino_string<P:Polyomino>(contents: &str) -> Result<Vec<P>, Error> {
+// Characters that mark a cell in a pieces or board file. Spaces are gaps.
+pub(crate) fn is_cell(c: char) -> bool {
+    c == 'X' || c == '#'
+}
+
+// The layers of a solid piece or board are drawn one after the other, with a
+// line of dashes between each layer and the next
+pub(crate) fn is_layer_break(line: &str) -> bool {
+    !line.is_empty() && line.chars().all(|c| c == '-')
+}
+
+// A piece drawn with a letter other than X is named by that letter. Pieces
+// drawn with X or # are given names by name_pieces. A '.' is a gap, which
+// keeps the rows of the layers of a solid piece lined up when the first one
+// of a layer is empty.
+pub fn read_polyomino_string<P:Polyomino>(contents: &str) -> Result<Vec<P>, ParseError> {
     let mut res = Vec::new();
 
-    let mut count = 0;
-    let mut points = Vec::new();
+    // The canonical form of each piece so far, and the line the piece starts on
+    let mut seen = FxHashMap::default();
+
+    // Each name given in the file, and the line its piece starts on
+    let mut names = FxHashMap::default();
+
+    // The cells of the current piece as (line, column), in reading order,
+    // and the name each one is drawn with. A solid piece can have several
+    // layers, and each one starts on a line of its own.
+    let mut cells = Vec::new();
+    let mut labels = Vec::new();
+    let mut layer_starts = Vec::new();
+
+    // The blank line added at the end finishes the last piece
+    for (n, line) in contents.lines().chain(iter::once("")).enumerate() {
+        // polyominoes are separated by blank lines.
+        if line.chars().all(|c| c == ' ') {
+            if cells.is_empty() {
+                layer_starts.clear();
+                continue;
+            }
+
+            let mut piece: P = build_piece(&cells, &layer_starts)?;
+            let (line, column) = cells[0];
+            // Solids that are mirror images of each other are different pieces
+            let canonical = if P::Pt::LATTICE == Lattice::Cubic { piece.canonical_one_sided() } else { piece.canonical_free() };
+            if let Some(&first_line) = seen.get(&canonical) {
+                return Err(ParseError::DuplicatePiece { line: line + 1, column: column + 1, first_line });
+            }
 
-    for line in contents.split('\n') {
-        match line {
-            // polyominoes are separated by empty lines.
-            "" => {
-                res.push(P::new(points));
-                points = Vec::new();
-                count = 0;
+            if let Some(i) = labels.iter().position(|&l| l != labels[0]) {
+                let (line, column) = cells[i];
+                return Err(ParseError::MixedNames { line: line + 1, column: column + 1 });
             }
-            // anything else is a definition
-            str => {
-                for (i, c) in str.chars().enumerate() {
-                    if c != ' ' {
-                        points.push(P::Pt::new(count, i as i16));
-                    }
+
+            if let Some(name) = labels[0] {
+                if let Some(&first_line) = names.get(&name) {
+                    return Err(ParseError::DuplicateName { line: line + 1, column: column + 1, first_line });
                 }
-                count += 1;
+                names.insert(name, line + 1);
+            }
+
+            seen.insert(canonical, line + 1);
+            piece.set_name(labels[0]);
+            res.push(piece);
+            cells.clear();
+            labels.clear();
+            layer_starts.clear();
+            continue;
+        }
+
+        if P::Pt::LATTICE == Lattice::Cubic && !layer_starts.is_empty() && is_layer_break(line) {
+            layer_starts.push(n + 1);
+            continue;
+        }
+
+        // anything else is a definition
+        if layer_starts.is_empty() {
+            layer_starts.push(n);
+        }
+        for (column, c) in line.chars().enumerate() {
+            if is_cell(c) || c.is_ascii_alphabetic() {
+                cells.push((n, column));
+                labels.push(if is_cell(c) { None } else { Some(c) });
+            } else if c != ' ' && c != '.' {
+                return Err(ParseError::InvalidCharacter { line: n + 1, column: column + 1, found: c });
             }
         }
     }
 
-    if !points.is_empty() {
-        res.push(P::new(points));
+    if res.is_empty() {
+        return Err(ParseError::Empty);
     }
 
+    name_pieces(&mut res);
     Ok(res)
 }
+
+// The shapes with well known names, in the same format as a pieces file. The
+// pentominoes have the letters of Golomb and Conway.
+const STANDARD_NAMES: &[(char, &str)] = &[
+    ('I', "XX"),
+    ('I', "XXX"),
+    ('L', "XX\nX"),
+    ('I', "XXXX"),
+    ('L', "XXX\nX"),
+    ('O', "XX\nXX"),
+    ('S', "XX\n XX"),
+    ('T', "XXX\n X"),
+    ('F', " XX\nXX\n X"),
+    ('I', "XXXXX"),
+    ('L', "XXXX\nX"),
+    ('N', "XX\n XXX"),
+    ('P', "XX\nXX\nX"),
+    ('T', "XXX\n X\n X"),
+    ('U', "X X\nXXX"),
+    ('V', "X\nX\nXXX"),
+    ('W', "X\nXX\n XX"),
+    ('X', " X\nXXX\n X"),
+    ('Y', "XXXX\n X"),
+    ('Z', "XX\n X\n XX"),
+];
+
+// Names the pieces that don't have a name yet. A polyomino gets the standard
+// letter for its shape if it has one and no other piece is using it, and
+// otherwise the first letter from A to Z and then a to z that is still free.
+// Once all 52 letters are used the rest are left without a name.
+pub fn name_pieces<P:Polyomino>(pieces: &mut [P]) {
+    let standard: Vec<(char, P)> = STANDARD_NAMES
+        .iter()
+        .filter(|_| P::Pt::LATTICE == Lattice::Square)
+        .map(|&(name, drawing)| {
+            let cells = drawing.lines().enumerate().flat_map(|(line, row)| {
+                row.char_indices().filter(|&(_, c)| c == 'X').map(move |(column, _)| P::Pt::new(line as i16, column as i16))
+            });
+            (name, P::new(cells.collect()).canonical_free())
+        })
+        .collect();
+
+    let mut used: FxHashSet<char> = pieces.iter().filter_map(|p| p.name()).collect();
+
+    for p in pieces.iter_mut().filter(|p| p.name().is_none()) {
+        let canonical = p.canonical_free();
+        let found = standard.iter().find(|(name, shape)| *shape == canonical && !used.contains(name));
+
+        if let Some(&(name, _)) = found {
+            p.set_name(Some(name));
+            used.insert(name);
+        }
+    }
+
+    let mut free = ('A'..='Z').chain('a'..='z').filter(|c| !used.contains(c));
+    for p in pieces.iter_mut().filter(|p| p.name().is_none()) {
+        p.set_name(free.next());
+    }
+}
+
+// The piece with the given cells, moved so that its smallest x and y are 0.
+// On a square grid the line in the file is the x coordinate and the column is
+// the y coordinate. Other lattices aren't the same when they are turned on
+// their side, so their pieces are drawn as they are on a board, with the
+// column as the x coordinate and the first line of the piece, or of each of
+// its layers, as row 0.
+fn build_piece<P:Polyomino>(cells: &[(usize, usize)], layer_starts: &[usize]) -> Result<P, ParseError> {
+    let square = P::Pt::LATTICE == Lattice::Square;
+    let position = |(line, column): (usize, usize)| {
+        if square {
+            return (line as i16, column as i16, 0);
+        }
+
+        let z = layer_starts.partition_point(|&start| start <= line) - 1;
+        (column as i16, (line - layer_starts[z]) as i16, z as i16)
+    };
+
+    // Find the cells that can be reached from the first one
+    let mut joined = vec![false; cells.len()];
+    let mut pending = vec![0];
+    joined[0] = true;
+
+    while let Some(i) = pending.pop() {
+        let (x, y, z) = position(cells[i]);
+
+        for (j, &cell) in cells.iter().enumerate() {
+            let (cx, cy, cz) = position(cell);
+            if !joined[j] && P::Pt::neighbours(x, y).contains(&(cx - x, cy - y, cz - z)) {
+                joined[j] = true;
+                pending.push(j);
+            }
+        }
+    }
+
+    if let Some(i) = joined.iter().position(|&j| !j) {
+        return Err(ParseError::DisconnectedPiece { line: cells[i].0 + 1, column: cells[i].1 + 1 });
+    }
+
+    Ok(P::new(cells.iter().map(|&cell| { let (x, y, z) = position(cell); P::Pt::new_3d(x, y, z) }).collect()).normalize())
+}
@@ -0,0 +1,428 @@
+use std::fmt;
+
+use rustc_hash::FxHashSet;
+
+use crate::board::board_utils;
+use crate::board::Board;
+use crate::board::BoardState;
+use crate::placement::Placement;
+use crate::point::Lattice;
+use crate::point::Point;
+use crate::point::SimplePoint;
+use crate::polyomino::Polyomino;
+use crate::solver::Multiplicity;
+use crate::utils;
+
+/// Something wrong with a proposed solution. Pieces are given by their index
+/// among the candidates and cells as (x, y), counting from the top left, or
+/// as (x, y, z) in a layer after the first of a solid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// There is no candidate with this index.
+    UnknownPiece(usize),
+    /// The candidate has no variation with this index.
+    UnknownOrientation { piece: usize, orientation: usize },
+    /// The cells drawn with this letter, starting at `cell`, are not a
+    /// variation of any of the pieces.
+    UnknownShape { letter: char, cell: SimplePoint },
+    /// The piece is placed with its origin at a cell of another shape, such
+    /// as a triangle that points the other way, which would turn it upside
+    /// down.
+    Misaligned { piece: usize, anchor: SimplePoint },
+    /// The piece covers a cell that isn't part of the board.
+    OffBoard { piece: usize, cell: SimplePoint },
+    /// The piece covers a cell that another piece already covers.
+    Overlap { piece: usize, cell: SimplePoint },
+    /// The piece covers a cell that has to stay uncovered.
+    CoversReserved { piece: usize, cell: SimplePoint },
+    /// More cells are left uncovered than allowed.
+    Uncovered(Vec<SimplePoint>),
+    /// The piece is used more often than allowed.
+    TooMany { piece: usize, count: usize, max: usize },
+    /// The piece is used less often than required.
+    TooFew { piece: usize, count: usize, min: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::UnknownPiece(piece) => write!(f, "there is no piece {}", piece),
+            Violation::UnknownOrientation { piece, orientation } =>
+                write!(f, "piece {} has no orientation {}", piece, orientation),
+            Violation::UnknownShape { letter, cell } =>
+                write!(f, "the piece drawn with '{}' at {} is not one of the pieces", letter, position(cell)),
+            Violation::Misaligned { piece, anchor } =>
+                write!(f, "piece {} can't be placed at {} without turning it upside down", piece, position(anchor)),
+            Violation::OffBoard { piece, cell } =>
+                write!(f, "piece {} covers {}, which is not on the board", piece, position(cell)),
+            Violation::Overlap { piece, cell } =>
+                write!(f, "piece {} covers {}, which is already covered", piece, position(cell)),
+            Violation::CoversReserved { piece, cell } =>
+                write!(f, "piece {} covers {}, which has to stay uncovered", piece, position(cell)),
+            Violation::Uncovered(cells) => {
+                write!(f, "{} cells are left uncovered:", cells.len())?;
+                for cell in cells {
+                    write!(f, " {}", position(cell))?;
+                }
+                Ok(())
+            }
+            Violation::TooMany { piece, count, max } =>
+                write!(f, "piece {} is used {} times, but at most {}", piece, count, max),
+            Violation::TooFew { piece, count, min } =>
+                write!(f, "piece {} is used {} times, but at least {}", piece, count, min),
+        }
+    }
+}
+
+fn position(cell: &SimplePoint) -> String {
+    match cell.z {
+        0 => format!("({}, {})", cell.x, cell.y),
+        z => format!("({}, {}, {})", cell.x, cell.y, z),
+    }
+}
+
+/// Checks solutions made elsewhere against the same rules as a `Solver`
+/// with the same board, candidates and settings.
+pub struct Verifier<'a, 'b, P: Polyomino> {
+    board: &'b Board<'a, P>,
+    candidates: &'a [Vec<P>],
+    multiplicities: Vec<Multiplicity>,
+    max_uncovered: usize,
+}
+
+impl<'a, 'b, P: Polyomino> Verifier<'a, 'b, P> {
+    pub fn new(b: &'b Board<'a, P>, c: &'a [Vec<P>]) -> Verifier<'a, 'b, P> {
+        Verifier {
+            board: b,
+            candidates: c,
+            multiplicities: vec![Multiplicity::ONCE; c.len()],
+            max_uncovered: 0,
+        }
+    }
+
+    // As Solver::set_multiplicity
+    pub fn set_multiplicity(&mut self, i: usize, multiplicity: Multiplicity) {
+        self.multiplicities[i] = multiplicity;
+    }
+
+    // As Solver::set_max_uncovered
+    pub fn set_max_uncovered(&mut self, cells: usize) {
+        self.max_uncovered = cells;
+    }
+
+    /// The board with the pieces in place, or everything that is wrong with
+    /// the placements. A placement that doesn't fit is left off the board.
+    pub fn verify(&self, placements: &[Placement]) -> Result<Board<'a, P>, Vec<Violation>> {
+        let mut b = self.board.clone();
+        let mut violations = Vec::new();
+
+        let mut counts = vec![0; self.candidates.len()];
+        for i in board_utils::fixed_candidates(self.board, self.candidates) {
+            counts[i] += 1;
+        }
+
+        for placement in placements {
+            let piece = placement.candidate;
+            let Some(variations) = self.candidates.get(piece) else {
+                violations.push(Violation::UnknownPiece(piece));
+                continue;
+            };
+            let Some(p) = variations.get(placement.variation) else {
+                violations.push(Violation::UnknownOrientation { piece, orientation: placement.variation });
+                continue;
+            };
+            counts[piece] += 1;
+
+            if !P::Pt::is_translation(placement.anchor.x(), placement.anchor.y()) {
+                violations.push(Violation::Misaligned { piece, anchor: placement.anchor });
+                continue;
+            }
+
+            let problem = p.iter().find_map(|pt| {
+                let anchor = placement.anchor;
+                let cell = SimplePoint::new_3d(pt.x() + anchor.x, pt.y() + anchor.y, pt.z() + anchor.z);
+                match b.get_3d(cell.x, cell.y, cell.z) {
+                    BoardState::Empty => None,
+                    BoardState::Void => Some(Violation::OffBoard { piece, cell }),
+                    BoardState::Full(..) | BoardState::Fixed(_) => Some(Violation::Overlap { piece, cell }),
+                    BoardState::Reserved => Some(Violation::CoversReserved { piece, cell }),
+                }
+            });
+
+            match problem {
+                Some(violation) => violations.push(violation),
+                None => {
+                    b.add_polyomino(p, &placement.anchor);
+                }
+            }
+        }
+
+        for (piece, (&count, m)) in counts.iter().zip(&self.multiplicities).enumerate() {
+            if m.max.is_some_and(|max| count > max) {
+                violations.push(Violation::TooMany { piece, count, max: m.max.unwrap() });
+            }
+            if count < m.min {
+                violations.push(Violation::TooFew { piece, count, min: m.min });
+            }
+        }
+
+        // As with the solver, the board needn't be covered when every piece
+        // is used as often as it can be
+        let uncovered = board_utils::get_all_unoccupied(&b);
+        let all_used = counts.iter().zip(&self.multiplicities).all(|(&count, m)| m.max == Some(count));
+        if uncovered.len() > self.max_uncovered && !all_used {
+            violations.push(Violation::Uncovered(uncovered));
+        }
+
+        if violations.is_empty() {
+            Ok(b)
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Checks a solution drawn as a grid of letters, one for each cell, as
+    /// `polyrun --format letters` prints them. A `.` or a space is an
+    /// uncovered cell and the cells of a pre-placed piece can be drawn with
+    /// its letter or left out. Each group of touching cells with the same
+    /// letter is a piece, which is taken to be the candidate with that name if
+    /// it has the right shape, or otherwise one that does. The layers of a
+    /// solid are separated by a line of dashes.
+    pub fn verify_letters(&self, grid: &str) -> Result<Board<'a, P>, Vec<Violation>> {
+        // The rows of each layer of the board
+        let mut layers: Vec<Vec<Vec<char>>> = vec![Vec::new()];
+        for line in grid.lines() {
+            if P::Pt::LATTICE == Lattice::Cubic && utils::is_layer_break(line) {
+                layers.push(Vec::new());
+                continue;
+            }
+            layers.last_mut().unwrap().push(line.chars().collect());
+        }
+        let letter = |x: i16, y: i16, z: i16| -> Option<char> {
+            if x < 0 || y < 0 || z < 0 {
+                return None;
+            }
+            let c = *layers.get(z as usize)?.get(y as usize)?.get(x as usize)?;
+            match (c, self.board.get_3d(x, y, z)) {
+                ('.' | ' ', _) => None,
+                (c, BoardState::Fixed(label)) if c == label => None,
+                (c, _) => Some(c),
+            }
+        };
+
+        let mut counts = vec![0; self.candidates.len()];
+        for i in board_utils::fixed_candidates(self.board, self.candidates) {
+            counts[i] += 1;
+        }
+
+        let mut seen = FxHashSet::default();
+        let mut placements = Vec::new();
+        let mut violations = Vec::new();
+
+        for (z, rows) in layers.iter().enumerate() {
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..row.len() {
+                    let start = (x as i16, y as i16, z as i16);
+                    let Some(c) = letter(start.0, start.1, start.2) else { continue };
+                    if !seen.insert(start) {
+                        continue;
+                    }
+
+                    // The piece is every cell joined to this one through cells
+                    // with the same letter
+                    let mut cells = vec![start];
+                    let mut i = 0;
+                    while i < cells.len() {
+                        let (cx, cy, cz) = cells[i];
+                        for &(dx, dy, dz) in P::Pt::neighbours(cx, cy) {
+                            let next = (cx + dx, cy + dy, cz + dz);
+                            if letter(next.0, next.1, next.2) == Some(c) && seen.insert(next) {
+                                cells.push(next);
+                            }
+                        }
+                        i += 1;
+                    }
+
+                    match self.identify(c, &cells, &counts) {
+                        Some(placement) => {
+                            counts[placement.candidate] += 1;
+                            placements.push(placement);
+                        }
+                        None => violations.push(Violation::UnknownShape { letter: c, cell: SimplePoint::new_3d(start.0, start.1, start.2) }),
+                    }
+                }
+            }
+        }
+
+        match self.verify(&placements) {
+            Ok(b) if violations.is_empty() => Ok(b),
+            Ok(_) => Err(violations),
+            Err(mut rest) => {
+                violations.append(&mut rest);
+                Err(violations)
+            }
+        }
+    }
+
+    // The placement of a variation with exactly these cells, preferring the
+    // candidate named `letter` and then ones that can still be used
+    fn identify(&self, letter: char, cells: &[(i16, i16, i16)], counts: &[usize]) -> Option<Placement> {
+        let shape = normalized(cells.iter().copied());
+
+        let mut order: Vec<usize> = (0..self.candidates.len()).collect();
+        order.sort_by_key(|&i| {
+            let named = self.candidates[i].first().is_some_and(|p| p.name() == Some(letter));
+            let spare = self.multiplicities[i].max.is_none_or(|max| counts[i] < max);
+            (!named, !spare)
+        });
+
+        let min = |cells: &mut dyn Iterator<Item = (i16, i16, i16)>| {
+            cells.fold((i16::MAX, i16::MAX, i16::MAX), |m, c| (m.0.min(c.0), m.1.min(c.1), m.2.min(c.2)))
+        };
+        let (cx, cy, cz) = min(&mut cells.iter().copied());
+
+        // A variation with the same shape only matches if it can be moved
+        // onto the cells, which on a triangular lattice it may not be
+        order.into_iter().find_map(|i| {
+            self.candidates[i].iter().enumerate().find_map(|(v, variation)| {
+                let (vx, vy, vz) = min(&mut variation.iter().map(|pt| (pt.x(), pt.y(), pt.z())));
+                let matches = P::Pt::is_translation(cx - vx, cy - vy)
+                    && normalized(variation.iter().map(|pt| (pt.x(), pt.y(), pt.z()))) == shape;
+
+                matches.then(|| Placement { candidate: i, variation: v, anchor: SimplePoint::new_3d(cx - vx, cy - vy, cz - vz) })
+            })
+        })
+    }
+}
+
+// The cells moved so that the smallest x, y and z are 0, in order
+fn normalized(cells: impl Iterator<Item = (i16, i16, i16)>) -> Vec<(i16, i16, i16)> {
+    let cells: Vec<(i16, i16, i16)> = cells.collect();
+    let min_x = cells.iter().map(|c| c.0).min().unwrap_or(0);
+    let min_y = cells.iter().map(|c| c.1).min().unwrap_or(0);
+    let min_z = cells.iter().map(|c| c.2).min().unwrap_or(0);
+
+    let mut res: Vec<(i16, i16, i16)> = cells.iter().map(|&(x, y, z)| (x - min_x, y - min_y, z - min_z)).collect();
+    res.sort();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::placement;
+    use crate::placement::Placement;
+    use crate::point::CubePoint;
+    use crate::point::Point;
+    use crate::point::SimplePoint;
+    use crate::polyomino::Polyomino;
+    use crate::polyomino::SimplePolyomino;
+    use crate::solver::Multiplicity;
+    use crate::solver::Solver;
+    use crate::utils;
+    use crate::utils::PredefinedPolyominoes;
+    use crate::utils::Restrictions;
+    use crate::verify::Verifier;
+    use crate::verify::Violation;
+
+    type Poly = SimplePolyomino<SimplePoint>;
+
+    fn pentominoes() -> Vec<Vec<Poly>> {
+        let pentominoes: Vec<Poly> = utils::get_polyominoes(PredefinedPolyominoes::Pentominoes).unwrap();
+        utils::build_variations(&pentominoes, Restrictions::None)
+    }
+
+    fn letters(grid: &[&str]) -> String {
+        grid.iter().map(|row| format!("{}\n", row)).collect()
+    }
+
+    #[test]
+    fn solver_solutions() {
+        let polys = pentominoes();
+        let board = Board::new(10, 6);
+        let mut b = board.clone();
+        let mut solver = Solver::new(&mut b, &polys);
+        let verifier = Verifier::new(&board, &polys);
+
+        for s in solver.solutions().take(5) {
+            let placements = placement::placements(&s, &polys).unwrap();
+            assert_eq!(verifier.verify(&placements).map(|b| b.to_string()).ok(), Some(s.to_string()));
+
+            let grid: String = s.row_range()
+                .map(|y| s.col_range().map(|x| s.get(x, y).to_string()).collect::<String>() + "\n")
+                .collect();
+            assert_eq!(verifier.verify_letters(&grid).map(|b| b.to_string()).ok(), Some(s.to_string()));
+        }
+    }
+
+    #[test]
+    fn solid_letters() {
+        let soma: Vec<SimplePolyomino<CubePoint>> = utils::read_polyominoes_from_file("data/soma.poly").unwrap();
+        let polys = utils::build_variations(&soma, Restrictions::None);
+        let board = Board::from_file("data/soma.board").unwrap();
+        let mut b = board.clone();
+        let mut solver = Solver::new(&mut b, &polys);
+        let verifier = Verifier::new(&board, &polys);
+
+        // The layers are separated by dashes, as on the board
+        let s = solver.solutions().next().unwrap();
+        assert_eq!(s.to_string().lines().filter(|line| *line == "-").count(), 2);
+        assert_eq!(verifier.verify_letters(&s.to_string()).map(|b| b.to_string()).ok(), Some(s.to_string()));
+        assert!(verifier.verify_letters(&s.to_string().replacen('-', "", 1)).is_err());
+    }
+
+    #[test]
+    fn violations() {
+        let polys = pentominoes();
+        let board = Board::new(10, 6);
+        let verifier = Verifier::new(&board, &polys);
+        let solution = letters(&["IYTTTWWXUU",
+                                 "IYYTWWXXXU",
+                                 "IYFTWPZXUU",
+                                 "IYFFPPZZZV",
+                                 "IFFLPPNNZV",
+                                 "LLLLNNNVVV"]);
+        assert!(verifier.verify_letters(&solution).is_ok());
+
+        // Swapping two cells turns the X into a second F, and leaves a U
+        // cell on its own
+        let swapped = verifier.verify_letters(&solution.replacen("XUU", "UXU", 1)).err().unwrap();
+        let piece = |name| polys.iter().position(|vs| vs[0].name() == Some(name)).unwrap();
+
+        assert!(swapped.contains(&Violation::UnknownShape { letter: 'U', cell: SimplePoint::new(7, 0) }));
+        assert!(swapped.contains(&Violation::UnknownShape { letter: 'U', cell: SimplePoint::new(9, 0) }));
+        assert!(swapped.contains(&Violation::TooMany { piece: piece('F'), count: 2, max: 1 }));
+        assert!(swapped.contains(&Violation::TooFew { piece: piece('X'), count: 0, min: 1 }));
+        assert!(swapped.contains(&Violation::TooFew { piece: piece('U'), count: 0, min: 1 }));
+        assert_eq!(swapped.len(), 6);
+        assert!(matches!(&swapped[5], Violation::Uncovered(cells) if cells.len() == 5));
+        assert_eq!(swapped[5].to_string(), "5 cells are left uncovered: (7, 0) (8, 2) (9, 0) (9, 1) (9, 2)");
+    }
+
+    #[test]
+    fn placements() {
+        let polys = pentominoes();
+        let mut board = Board::new(5, 5);
+        board.reserve(&SimplePoint::new(3, 4));
+        let mut verifier = Verifier::new(&board, &polys);
+        for i in 0..polys.len() {
+            verifier.set_multiplicity(i, Multiplicity::OPTIONAL);
+        }
+        verifier.set_max_uncovered(25);
+
+        let at = |candidate, variation, x, y| Placement { candidate, variation, anchor: SimplePoint::new(x, y) };
+        let x = polys.iter().position(|vs| vs.len() == 1).unwrap();
+
+        assert!(verifier.verify(&[at(x, 0, 0, 0)]).is_ok());
+        assert_eq!(verifier.verify(&[at(x, 0, 0, 0), at(x, 0, 2, 0)]).err(),
+                   Some(vec![Violation::Overlap { piece: x, cell: SimplePoint::new(2, 1) },
+                            Violation::TooMany { piece: x, count: 2, max: 1 }]));
+        assert_eq!(verifier.verify(&[at(x, 0, 3, 0)]).err(), Some(vec![Violation::OffBoard { piece: x, cell: SimplePoint::new(5, 1) }]));
+        assert_eq!(verifier.verify(&[at(x, 0, 2, 2)]).err(), Some(vec![Violation::CoversReserved { piece: x, cell: SimplePoint::new(3, 4) }]));
+        assert_eq!(verifier.verify(&[at(12, 0, 0, 0), at(x, 1, 0, 0)]).err(),
+                   Some(vec![Violation::UnknownPiece(12), Violation::UnknownOrientation { piece: x, orientation: 1 }]));
+
+        verifier.set_max_uncovered(18);
+        assert_eq!(verifier.verify(&[at(x, 0, 0, 0)]).err().map(|v| v.len()), Some(1));
+    }
+}